@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mal_rust::core::env_core;
+use mal_rust::env::{evaluate, Env};
+use mal_rust::tokenizer::Tokenizer;
+use mal_rust::types::{Map, Sexp};
+
+const SIZE: i64 = 100_000;
+
+fn env_with_collections() -> Env {
+    let env = env_core();
+    let list = (0..SIZE).map(Sexp::Integer).collect();
+    let map = (0..SIZE)
+        .map(|i| (Sexp::Integer(i), Sexp::Integer(i)))
+        .collect::<Map>();
+    env.borrow_mut().set("xs", Sexp::Vec(list));
+    env.borrow_mut().set("m", Sexp::Map(map));
+    env
+}
+
+fn bench_form(c: &mut Criterion, env: &Env, form: &str) {
+    let ast = Sexp::read_from(&mut Tokenizer::new(form.to_string())).unwrap();
+    c.bench_function(form, |b| {
+        b.iter(|| evaluate(black_box(ast.clone()), env.clone()).unwrap())
+    });
+}
+
+/** Each op on a 100k element collection should cost about the same as on an empty one */
+fn collections(c: &mut Criterion) {
+    let env = env_with_collections();
+    for form in [
+        "xs",
+        "(rest xs)",
+        "(cons 0 xs)",
+        "(conj xs 0)",
+        "(assoc xs 50000 0)",
+        "(nth xs 50000)",
+        "(assoc m -1 0)",
+        "(get m 50000)",
+        "(dissoc m 50000)",
+    ] {
+        bench_form(c, &env, form);
+    }
+}
+
+criterion_group!(benches, collections);
+criterion_main!(benches);
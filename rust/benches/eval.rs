@@ -0,0 +1,36 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mal_rust::core::env_core;
+use mal_rust::env::{self, Env};
+use mal_rust::tokenizer::Tokenizer;
+use mal_rust::types::Sexp;
+use mal_rust::vm;
+
+fn eval_str(env: &Env, src: &str) -> Sexp {
+    let ast = Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap();
+    env::evaluate(ast, env.clone()).unwrap()
+}
+
+/** Call-heavy recursion on the tree-walker and the bytecode VM */
+fn recursion(c: &mut Criterion) {
+    let env = env_core();
+    eval_str(
+        &env,
+        "(def! fib (fn* (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))",
+    );
+    eval_str(
+        &env,
+        "(def! sumdown (fn* (n) (let* (m (- n 1)) (if (> n 0) (+ n (sumdown m)) 0))))",
+    );
+    for form in ["(fib 20)", "(sumdown 1000)"] {
+        let ast = Sexp::read_from(&mut Tokenizer::new(form.to_string())).unwrap();
+        c.bench_function(&format!("tree {}", form), |b| {
+            b.iter(|| env::evaluate(black_box(ast.clone()), env.clone()).unwrap())
+        });
+        c.bench_function(&format!("vm {}", form), |b| {
+            b.iter(|| vm::evaluate(black_box(ast.clone()), env.clone()).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, recursion);
+criterion_main!(benches);
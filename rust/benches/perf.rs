@@ -0,0 +1,102 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mal_rust::env::{self, apply};
+use mal_rust::interpreter::Interpreter;
+use mal_rust::lazy;
+use mal_rust::tokenizer::Tokenizer;
+use mal_rust::types::{Arity, Native, Sexp};
+use mal_rust::vm;
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+/**
+ * Interpreter with what `tests/perf*.mal` use beyond this crate: atoms, as
+ * natives over a shared cell that read with no args and store with one, and
+ * `concat`. The `and`, `or`, `cond`, `->` and `->>` macros of `core.mal` are
+ * expanded by the resolver already.
+ */
+fn interp_with_harness() -> Interpreter {
+    let interp = Interpreter::new();
+    interp.define_native("atom", Arity::exactly(1), |args, _| {
+        let cell = Rc::new(RefCell::new(args[0].clone()));
+        let atom = Native::new("atom", Arity::between(0, 1), move |args, _| {
+            if let [val] = args {
+                *cell.borrow_mut() = val.clone();
+            }
+            Ok(cell.borrow().clone())
+        });
+        Ok(Sexp::Func(atom))
+    });
+    interp.define_native("deref", Arity::exactly(1), |args, env| {
+        apply(&args[0], Vec::new(), env)
+    });
+    interp.define_native("swap!", Arity::at_least(2), |args, env| {
+        let mut call = vec![apply(&args[0], Vec::new(), env)?];
+        call.extend_from_slice(&args[2..]);
+        let val = apply(&args[1], call, env)?;
+        apply(&args[0], vec![val], env)
+    });
+    interp.define_native("concat", Arity::at_least(0), |args, _| {
+        let mut items = Vec::new();
+        for arg in args {
+            for item in lazy::iter(arg) {
+                items.push(item?);
+            }
+        }
+        Ok(Sexp::List(items.into()))
+    });
+    interp
+}
+
+/**
+ * What a perf file times: the body of `(time ...)`, or one call of the
+ * function given to `(run-fn-for f secs)`, which criterion repeats instead
+ */
+fn measured(form: &Sexp) -> Option<Sexp> {
+    let Sexp::List(list) = form else {
+        return None;
+    };
+    match list.front() {
+        Some(Sexp::Symbol(sym)) if sym.as_str() == "time" => list.get(1).cloned(),
+        Some(Sexp::Symbol(sym)) if sym.as_str() == "run-fn-for" => {
+            Some(Sexp::List(list.skip(1).take(1)))
+        }
+        _ => list.iter().find_map(measured),
+    }
+}
+
+fn is_load_file(form: &Sexp) -> bool {
+    matches!(form, Sexp::List(list) if matches!(list.front(), Some(Sexp::Symbol(sym)) if sym.as_str() == "load-file"))
+}
+
+/**
+ * Each `tests/perf*.mal` on the tree-walker and the bytecode VM. Their
+ * `load-file` of `core.mal` and `perf.mal` is skipped, as the prelude and
+ * `interp_with_harness` stand in for both.
+ */
+fn perf_files(c: &mut Criterion) {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../tests");
+    for name in ["perf1", "perf2", "perf3"] {
+        let src = fs::read_to_string(format!("{}/{}.mal", dir, name)).unwrap();
+        let interp = interp_with_harness();
+        let env = interp.env();
+        for form in Sexp::read_all(&mut Tokenizer::new(src)).unwrap() {
+            if is_load_file(&form) {
+                continue;
+            }
+            let Some(body) = measured(&form) else {
+                env::evaluate(form, env.clone()).unwrap();
+                continue;
+            };
+            c.bench_function(&format!("tree {}", name), |b| {
+                b.iter(|| env::evaluate(black_box(body.clone()), env.clone()).unwrap())
+            });
+            c.bench_function(&format!("vm {}", name), |b| {
+                b.iter(|| vm::evaluate(black_box(body.clone()), env.clone()).unwrap())
+            });
+        }
+    }
+}
+
+criterion_group!(benches, perf_files);
+criterion_main!(benches);
@@ -1,20 +1,33 @@
-use mal_rust::env::evaluate;
-use mal_rust::core::env_core;
+use mal_rust::env::{self, Env};
+use mal_rust::core::{env_bare, env_core};
+use mal_rust::vm;
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 
+use mal_rust::lazy;
 use mal_rust::tokenizer::Tokenizer;
 use mal_rust::types::Sexp;
 
 const HIST_PATH: &str = ".mal-history";
 
-fn rep(input: String) -> Result<String, String> {
+type Evaluate = fn(Sexp, Env) -> Result<Sexp, String>;
+
+fn rep(input: String, new_env: fn() -> Env, evaluate: Evaluate) -> Result<String, String> {
     let ast = Sexp::read_from(&mut Tokenizer::new(input))?;
-    let output = evaluate(ast, env_core())?;
+    let output = evaluate(ast, new_env())?;
+    lazy::realise(&output)?;
     Ok(output.to_string())
 }
 
 fn main() -> Result<(), ReadlineError> {
+    let new_env: fn() -> Env = match std::env::args().any(|arg| arg == "--no-prelude") {
+        true => env_bare,
+        false => env_core,
+    };
+    let evaluate: Evaluate = match std::env::args().any(|arg| arg == "--vm") {
+        true => vm::evaluate,
+        false => env::evaluate,
+    };
     let mut rl = DefaultEditor::new()?;
     if rl.load_history(HIST_PATH).is_err() {
         eprintln!("History file '{}' not found", HIST_PATH);
@@ -27,7 +40,7 @@ fn main() -> Result<(), ReadlineError> {
                 }
                 rl.add_history_entry(buf.as_str())?;
                 rl.save_history(HIST_PATH)?;
-                match rep(buf) {
+                match rep(buf, new_env, evaluate) {
                     Ok(output) => println!("{}", output),
                     Err(error) => println!("[ERROR] {}", error),
                 };
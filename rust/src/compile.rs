@@ -0,0 +1,211 @@
+use crate::resolve::{Address, Expr, Global, Lambda};
+use crate::types::Sexp;
+use std::rc::Rc;
+
+/** Instructions for `vm::run`, operating on a value stack and the current frame */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /** Push `constants[i]` */
+    Const(usize),
+    Local(Address),
+    Global(Global),
+    /** Pop into a slot of the current frame */
+    SetLocal(usize),
+    /** Store the top of the stack without popping it, as `def!` returns its value */
+    DefGlobal(Global),
+    /** Enter a `let*` frame of the given size, nested in the current one */
+    PushFrame(usize),
+    PopFrame,
+    Pop,
+    Jump(usize),
+    /** Pop the condition and jump if it is `nil` or `false` */
+    JumpIfFalse(usize),
+    /** Push a closure over `lambdas[i]` capturing the current frame */
+    Closure(usize),
+    /** Call the function below `n` arguments */
+    Call(usize),
+    /** Like `Call`, but a closure replaces the current call frame */
+    TailCall(usize),
+    /** Install a handler at the given address until the matching `EndTry` */
+    Try(usize),
+    EndTry,
+    /** Pop `n` values into a `Sexp::Vec` */
+    Vector(usize),
+    Return,
+}
+
+/** Compiled body of a top level form or `fn*` */
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Sexp>,
+    pub lambdas: Vec<Rc<Lambda>>,
+    /** Body start, frame size and whether to renew the frame, for each enclosing loop */
+    loops: Vec<(usize, usize, bool)>,
+}
+
+/** Compiles a resolved form, ending with `Op::Return` */
+pub fn compile(expr: &Expr) -> Chunk {
+    let mut chunk = Chunk::default();
+    chunk.emit(expr, true);
+    chunk.code.push(Op::Return);
+    chunk
+}
+
+impl Chunk {
+    fn emit(&mut self, expr: &Expr, tail: bool) {
+        match expr {
+            Expr::Const(sexp) => {
+                self.constants.push(sexp.clone());
+                self.code.push(Op::Const(self.constants.len() - 1));
+            }
+            Expr::Local(address) => self.code.push(Op::Local(*address)),
+            Expr::Global(global) => self.code.push(Op::Global(*global)),
+            Expr::Def(global, val) => {
+                self.emit(val, false);
+                self.code.push(Op::DefGlobal(*global));
+            }
+            Expr::Let(size, bindings, body) => {
+                self.code.push(Op::PushFrame(*size));
+                for (slot, val) in bindings {
+                    self.emit(val, false);
+                    self.code.push(Op::SetLocal(*slot));
+                }
+                self.emit(body, tail);
+                self.code.push(Op::PopFrame);
+            }
+            Expr::Do(exprs) => match exprs.split_last() {
+                None => self.emit(&Expr::Const(Sexp::Nil), tail),
+                Some((last, init)) => {
+                    for expr in init {
+                        self.emit(expr, false);
+                        self.code.push(Op::Pop);
+                    }
+                    self.emit(last, tail);
+                }
+            },
+            Expr::If(cond, then, otherwise) => {
+                self.emit(cond, false);
+                let jump_else = self.placeholder();
+                self.emit(then, tail);
+                let jump_end = self.placeholder();
+                self.code[jump_else] = Op::JumpIfFalse(self.code.len());
+                self.emit(otherwise, tail);
+                self.code[jump_end] = Op::Jump(self.code.len());
+            }
+            Expr::Fn(lambda) => {
+                self.lambdas.push(lambda.clone());
+                self.code.push(Op::Closure(self.lambdas.len() - 1));
+            }
+            Expr::Call(func, args) => {
+                self.emit(func, false);
+                args.iter().for_each(|arg| self.emit(arg, false));
+                self.code.push(match tail {
+                    true => Op::TailCall(args.len()),
+                    false => Op::Call(args.len()),
+                });
+            }
+            Expr::Try(body, size, handler) => {
+                let install = self.placeholder();
+                self.emit(body, false);
+                self.code.push(Op::EndTry);
+                let jump_end = self.placeholder();
+                self.code[install] = Op::Try(self.code.len());
+                self.code.push(Op::PushFrame(*size));
+                self.code.push(Op::SetLocal(0));
+                self.emit(handler, tail);
+                self.code.push(Op::PopFrame);
+                self.code[jump_end] = Op::Jump(self.code.len());
+            }
+            Expr::Loop(size, bindings, body, captured) => {
+                self.code.push(Op::PushFrame(*size));
+                for (slot, val) in bindings {
+                    self.emit(val, false);
+                    self.code.push(Op::SetLocal(*slot));
+                }
+                self.loops.push((self.code.len(), *size, *captured));
+                self.emit(body, tail);
+                self.loops.pop();
+                self.code.push(Op::PopFrame);
+            }
+            Expr::Recur(depth, values) => {
+                values.iter().for_each(|(_, val)| self.emit(val, false));
+                (0..*depth).for_each(|_| self.code.push(Op::PopFrame));
+                let (start, size, captured) = *self.loops.last().expect("recur resolved in a loop");
+                if captured {
+                    self.code.push(Op::PopFrame);
+                    self.code.push(Op::PushFrame(size));
+                }
+                for (slot, _) in values.iter().rev() {
+                    self.code.push(Op::SetLocal(*slot));
+                }
+                self.code.push(Op::Jump(start));
+            }
+            Expr::Vec(exprs) => {
+                exprs.iter().for_each(|expr| self.emit(expr, false));
+                self.code.push(Op::Vector(exprs.len()));
+            }
+        }
+    }
+
+    /** Reserves a jump to be patched once its target is known */
+    fn placeholder(&mut self) -> usize {
+        self.code.push(Op::Return);
+        self.code.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::resolve;
+    use crate::symbol::{self, Symbol};
+    use crate::tokenizer::Tokenizer;
+
+    fn compile_str(src: &str) -> Chunk {
+        let ast = Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap();
+        compile(&resolve(&ast).unwrap())
+    }
+
+    #[test]
+    fn test_compile_if() {
+        let chunk = compile_str("(if x (f 1) 2)");
+        let x = Global::new(symbol::USER, Symbol::new("x"));
+        let f = Global::new(symbol::USER, Symbol::new("f"));
+        assert_eq!(
+            chunk.code,
+            [
+                Op::Global(x),
+                Op::JumpIfFalse(6),
+                Op::Global(f),
+                Op::Const(0),
+                Op::TailCall(1),
+                Op::Jump(7),
+                Op::Const(1),
+                Op::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_let() {
+        let chunk = compile_str("(let* (a 1) (do (g a) a))");
+        let g = Global::new(symbol::USER, Symbol::new("g"));
+        let a = Address { depth: 0, slot: 0 };
+        assert_eq!(
+            chunk.code,
+            [
+                Op::PushFrame(1),
+                Op::Const(0),
+                Op::SetLocal(0),
+                Op::Global(g),
+                Op::Local(a),
+                Op::Call(1),
+                Op::Pop,
+                Op::Local(a),
+                Op::PopFrame,
+                Op::Return,
+            ]
+        );
+    }
+}
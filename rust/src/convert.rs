@@ -0,0 +1,475 @@
+use crate::number::{self, Float};
+use crate::symbol::Symbol;
+use crate::types::{Arity, Map, Native, Sexp};
+use num_bigint::BigInt;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/** Why a `Sexp` could not be converted into a Rust value */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /** `found` is the printed value that had the wrong type */
+    WrongType {
+        expected: &'static str,
+        found: String,
+    },
+    /** A number of the right kind that doesn't fit in `target` */
+    OutOfRange { target: &'static str, value: String },
+    /** A tuple read from a sequence of another length */
+    WrongLength { expected: usize, found: usize },
+    /** A typed function called with another number of arguments */
+    WrongArity { expected: usize, found: usize },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::WrongType { expected, found } => {
+                write!(f, "expected {}, received {}", expected, found)
+            }
+            ConversionError::OutOfRange { target, value } => {
+                write!(f, "{} is out of range for {}", value, target)
+            }
+            ConversionError::WrongLength { expected, found } => {
+                write!(f, "expected {} elements, received {}", expected, found)
+            }
+            ConversionError::WrongArity { expected, found } => {
+                write!(f, "expected {} args, received {}", expected, found)
+            }
+        }
+    }
+}
+
+impl From<ConversionError> for String {
+    fn from(err: ConversionError) -> Self {
+        err.to_string()
+    }
+}
+
+fn wrong_type(expected: &'static str, sexp: &Sexp) -> ConversionError {
+    ConversionError::WrongType {
+        expected,
+        found: format!("{} {}", sexp.type_name(), sexp),
+    }
+}
+
+/** Rust values that can be read out of a `Sexp` */
+pub trait FromSexp: Sized {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError>;
+}
+
+/** Rust values that can be turned into a `Sexp` */
+pub trait IntoSexp {
+    fn into_sexp(self) -> Sexp;
+}
+
+impl FromSexp for Sexp {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        Ok(sexp.clone())
+    }
+}
+
+impl IntoSexp for Sexp {
+    fn into_sexp(self) -> Sexp {
+        self
+    }
+}
+
+/** Integers accept `Integer` and `BigInt`, and stay exact on the way back */
+macro_rules! integer {
+    ($($int:ty),*) => {$(
+        impl FromSexp for $int {
+            fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+                let big = match sexp {
+                    Sexp::Integer(_) | Sexp::BigInt(_) => number::to_big(sexp),
+                    _ => None,
+                }
+                .ok_or_else(|| wrong_type("Integer", sexp))?;
+                <$int>::try_from(&big).map_err(|_| ConversionError::OutOfRange {
+                    target: stringify!($int),
+                    value: big.to_string(),
+                })
+            }
+        }
+
+        impl IntoSexp for $int {
+            fn into_sexp(self) -> Sexp {
+                number::normalize(BigInt::from(self))
+            }
+        }
+    )*};
+}
+
+integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl FromSexp for BigInt {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::Integer(_) | Sexp::BigInt(_) => number::to_big(sexp),
+            _ => None,
+        }
+        .ok_or_else(|| wrong_type("Integer", sexp))
+    }
+}
+
+impl IntoSexp for BigInt {
+    fn into_sexp(self) -> Sexp {
+        number::normalize(self)
+    }
+}
+
+/** Floats accept any number, converting exact ones */
+impl FromSexp for f64 {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        number::to_f64(sexp).ok_or_else(|| wrong_type("number", sexp))
+    }
+}
+
+impl IntoSexp for f64 {
+    fn into_sexp(self) -> Sexp {
+        Sexp::Float(Float(self))
+    }
+}
+
+/** Finite numbers past `f32::MAX` are out of range rather than infinite */
+impl FromSexp for f32 {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        let num = f64::from_sexp(sexp)?;
+        match num as f32 {
+            narrow if narrow.is_infinite() && num.is_finite() => Err(ConversionError::OutOfRange {
+                target: "f32",
+                value: sexp.to_string(),
+            }),
+            narrow => Ok(narrow),
+        }
+    }
+}
+
+impl IntoSexp for f32 {
+    fn into_sexp(self) -> Sexp {
+        Sexp::Float(Float(self.into()))
+    }
+}
+
+impl FromSexp for bool {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::Bool(boolean) => Ok(*boolean),
+            _ => Err(wrong_type("Bool", sexp)),
+        }
+    }
+}
+
+impl IntoSexp for bool {
+    fn into_sexp(self) -> Sexp {
+        Sexp::Bool(self)
+    }
+}
+
+impl FromSexp for String {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::String(s) => Ok(s.clone()),
+            _ => Err(wrong_type("String", sexp)),
+        }
+    }
+}
+
+impl IntoSexp for String {
+    fn into_sexp(self) -> Sexp {
+        Sexp::String(self)
+    }
+}
+
+impl IntoSexp for &str {
+    fn into_sexp(self) -> Sexp {
+        Sexp::String(self.to_string())
+    }
+}
+
+/** Symbols and keywords both read as their interned name */
+impl FromSexp for Symbol {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::Symbol(sym) | Sexp::Keyword(sym) => Ok(*sym),
+            _ => Err(wrong_type("Symbol or Keyword", sexp)),
+        }
+    }
+}
+
+impl IntoSexp for () {
+    fn into_sexp(self) -> Sexp {
+        Sexp::Nil
+    }
+}
+
+/** `nil` is `None`, anything else must convert to `T` */
+impl<T: FromSexp> FromSexp for Option<T> {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::Nil => Ok(None),
+            sexp => T::from_sexp(sexp).map(Some),
+        }
+    }
+}
+
+impl<T: IntoSexp> IntoSexp for Option<T> {
+    fn into_sexp(self) -> Sexp {
+        self.map_or(Sexp::Nil, IntoSexp::into_sexp)
+    }
+}
+
+/** Lists, vectors and `nil` read as a `Vec`, which is written back as a vector */
+impl<T: FromSexp> FromSexp for Vec<T> {
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::List(list) | Sexp::Vec(list) => list.iter().map(T::from_sexp).collect(),
+            Sexp::Nil => Ok(Vec::new()),
+            _ => Err(wrong_type("List or Vec", sexp)),
+        }
+    }
+}
+
+impl<T: IntoSexp> IntoSexp for Vec<T> {
+    fn into_sexp(self) -> Sexp {
+        Sexp::Vec(self.into_iter().map(IntoSexp::into_sexp).collect())
+    }
+}
+
+impl<K, V, S> FromSexp for HashMap<K, V, S>
+where
+    K: FromSexp + Eq + Hash,
+    V: FromSexp,
+    S: BuildHasher + Default,
+{
+    fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+        match sexp {
+            Sexp::Map(map) => map
+                .iter()
+                .map(|(key, val)| Ok((K::from_sexp(key)?, V::from_sexp(val)?)))
+                .collect(),
+            Sexp::Nil => Ok(HashMap::default()),
+            _ => Err(wrong_type("Map", sexp)),
+        }
+    }
+}
+
+impl<K: IntoSexp, V: IntoSexp, S> IntoSexp for HashMap<K, V, S> {
+    fn into_sexp(self) -> Sexp {
+        Sexp::Map(
+            self.into_iter()
+                .map(|(key, val)| (key.into_sexp(), val.into_sexp()))
+                .collect::<Map>(),
+        )
+    }
+}
+
+/** Tuples read from a sequence of exactly their length, and write a vector */
+macro_rules! tuple {
+    ($len:literal; $($elem:ident $idx:tt),*) => {
+        impl<$($elem: FromSexp),*> FromSexp for ($($elem,)*) {
+            fn from_sexp(sexp: &Sexp) -> Result<Self, ConversionError> {
+                let (Sexp::List(list) | Sexp::Vec(list)) = sexp else {
+                    return Err(wrong_type("List or Vec", sexp));
+                };
+                if list.len() != $len {
+                    return Err(ConversionError::WrongLength {
+                        expected: $len,
+                        found: list.len(),
+                    });
+                }
+                Ok(($($elem::from_sexp(&list[$idx])?,)*))
+            }
+        }
+
+        impl<$($elem: IntoSexp),*> IntoSexp for ($($elem,)*) {
+            fn into_sexp(self) -> Sexp {
+                Sexp::Vec([$(self.$idx.into_sexp()),*].into_iter().collect())
+            }
+        }
+    };
+}
+
+tuple!(1; A 0);
+tuple!(2; A 0, B 1);
+tuple!(3; A 0, B 1, C 2);
+tuple!(4; A 0, B 1, C 2, D 3);
+
+/** Converts argument `index` of native `name`, naming both on failure */
+fn arg<T: FromSexp>(name: Symbol, args: &[Sexp], index: usize) -> Result<T, String> {
+    T::from_sexp(&args[index]).map_err(|err| format!("{} argument {}: {}", name, index + 1, err))
+}
+
+/**
+ * Rust functions over `FromSexp` arguments, called with a `&[Sexp]` that
+ * should hold `ARITY` elements. `Args` is the tuple of argument types.
+ */
+pub trait TypedFn<Args> {
+    const ARITY: usize;
+
+    fn call_typed(&self, name: Symbol, args: &[Sexp]) -> Result<Sexp, String>;
+}
+
+macro_rules! typed_fn {
+    ($arity:literal; $($arg:ident $idx:tt),*) => {
+        impl<F, R, $($arg),*> TypedFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Result<R, String>,
+            R: IntoSexp,
+            $($arg: FromSexp,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(unused_variables)]
+            fn call_typed(&self, name: Symbol, args: &[Sexp]) -> Result<Sexp, String> {
+                if args.len() != $arity {
+                    let err = ConversionError::WrongArity {
+                        expected: $arity,
+                        found: args.len(),
+                    };
+                    return Err(format!("{} {}", name, err));
+                }
+                self($(arg::<$arg>(name, args, $idx)?),*).map(IntoSexp::into_sexp)
+            }
+        }
+    };
+}
+
+typed_fn!(0;);
+typed_fn!(1; A 0);
+typed_fn!(2; A 0, B 1);
+typed_fn!(3; A 0, B 1, C 2);
+typed_fn!(4; A 0, B 1, C 2, D 3);
+
+impl Native {
+    /**
+     * Native from a Rust function over typed arguments. The argument count
+     * and each conversion are checked before `func` runs.
+     */
+    pub fn typed<Args, F>(name: impl Into<Symbol>, func: F) -> Self
+    where
+        F: TypedFn<Args> + 'static,
+    {
+        let name = name.into();
+        Native::new(name, Arity::exactly(F::ARITY), move |args, _env| {
+            func.call_typed(name, args)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::env_core;
+    use crate::tokenizer::Tokenizer;
+
+    fn read(src: &str) -> Sexp {
+        Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap()
+    }
+
+    fn from<T: FromSexp>(src: &str) -> Result<T, ConversionError> {
+        T::from_sexp(&read(src))
+    }
+
+    #[test]
+    fn test_from_sexp() {
+        assert_eq!(from::<i64>("42"), Ok(42));
+        assert_eq!(from::<u8>("255"), Ok(255));
+        assert_eq!(from::<u128>("18446744073709551616"), Ok(1 << 64));
+        assert_eq!(from::<f64>("1/2"), Ok(0.5));
+        assert_eq!(from::<bool>("false"), Ok(false));
+        assert_eq!(from::<String>("\"hi\""), Ok("hi".to_string()));
+        assert_eq!(from::<Option<i64>>("nil"), Ok(None));
+        assert_eq!(from::<Vec<i64>>("(1 2 3)"), Ok(vec![1, 2, 3]));
+        assert_eq!(from::<Vec<i64>>("nil"), Ok(vec![]));
+        assert_eq!(from::<(String, i64)>("[\"a\" 1]"), Ok(("a".to_string(), 1)));
+        let map = from::<HashMap<Symbol, Vec<bool>>>("{:a [true] :b []}").unwrap();
+        assert_eq!(map[&Symbol::new("a")], vec![true]);
+        assert!(map[&Symbol::new("b")].is_empty());
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        assert_eq!(
+            from::<i64>("\"1\""),
+            Err(ConversionError::WrongType {
+                expected: "Integer",
+                found: "String \"1\"".to_string()
+            })
+        );
+        assert_eq!(
+            from::<u8>("256"),
+            Err(ConversionError::OutOfRange {
+                target: "u8",
+                value: "256".to_string()
+            })
+        );
+        assert_eq!(
+            from::<(i64, i64)>("[1]"),
+            Err(ConversionError::WrongLength {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert!(matches!(
+            from::<Vec<i64>>("[1 :a]"),
+            Err(ConversionError::WrongType { .. })
+        ));
+        assert_eq!(
+            from::<f32>("-1e300"),
+            Err(ConversionError::OutOfRange {
+                target: "f32",
+                value: read("-1e300").to_string()
+            })
+        );
+        assert_eq!(
+            from::<i64>("1.5").unwrap_err().to_string(),
+            "expected Integer, received Float 1.5"
+        );
+    }
+
+    #[test]
+    fn test_into_sexp() {
+        assert_eq!(u64::MAX.into_sexp().to_string(), "18446744073709551615");
+        assert_eq!(Some(vec![(1, "a")]).into_sexp().to_string(), "[[1 \"a\"]]");
+        assert_eq!(None::<i64>.into_sexp().to_string(), "nil");
+        let map = HashMap::from([("k".to_string(), 2.5)]);
+        assert_eq!(map.into_sexp().to_string(), "{\"k\" 2.5}");
+        assert_eq!(().into_sexp().to_string(), "nil");
+    }
+
+    #[test]
+    fn test_typed_native() {
+        let env = env_core();
+        let clamp = |x: i64, lo: i64, hi: i64| Ok(x.clamp(lo, hi));
+        let join =
+            |parts: Vec<String>, sep: Option<String>| Ok(parts.join(sep.as_deref().unwrap_or(",")));
+        let clamp = Native::typed("clamp", clamp);
+        let join = Native::typed("join", join);
+        let call = |native: &Native, src: &str| match read(src) {
+            Sexp::Vec(args) => native
+                .call(&args.into_iter().collect::<Vec<_>>(), &env)
+                .map(|sexp| sexp.to_string()),
+            _ => unreachable!(),
+        };
+        assert_eq!(clamp.arity(), Arity::exactly(3));
+        assert_eq!(call(&clamp, "[15 0 10]"), Ok("10".to_string()));
+        assert_eq!(
+            call(&join, "[[\"a\" \"b\"] nil]"),
+            Ok("\"a,b\"".to_string())
+        );
+        assert_eq!(
+            call(&clamp, "[1 :lo 10]"),
+            Err("clamp argument 2: expected Integer, received Keyword :lo".to_string())
+        );
+        assert!(call(&clamp, "[1 2]").is_err());
+        let clamp = |x: i64, lo: i64, hi: i64| Ok(x.clamp(lo, hi));
+        assert_eq!(
+            clamp
+                .call_typed(Symbol::new("clamp"), &[Sexp::Integer(1)])
+                .map(|sexp| sexp.to_string()),
+            Err("clamp expected 3 args, received 1".to_string())
+        );
+    }
+}
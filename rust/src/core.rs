@@ -1,86 +1,553 @@
-use crate::env::{env_new, Env};
-use crate::types::{tokens_to_string as to_str, Sexp};
+use crate::env::{apply, env_new, evaluate, raise, truthy, with_rng, Env};
+use crate::json;
+use crate::lazy::{self, LazySeq};
+use crate::namespace;
+use crate::number::{self, Float};
+use crate::rng::Rng;
+use crate::tokenizer::Tokenizer;
+use crate::types::{tokens_to_string as to_str, Arity, Func, Map, Native, Sexp};
+use im_rc::Vector;
+use num_rational::BigRational;
+use std::cmp::Ordering;
 
+thread_local! {
+    /** Forms of `prelude.mal`, parsed once per thread rather than per env */
+    static PRELUDE: Vec<Sexp> = Sexp::read_all(&mut Tokenizer::new(
+        include_str!("prelude.mal").to_string(),
+    ))
+    .expect("prelude.mal should parse");
+}
+
+/**
+ * Env for `user`, over a root env of the natives and the definitions in
+ * `prelude.mal`, which every namespace shares
+ */
 pub fn env_core() -> Env {
+    let root = env_natives();
+    PRELUDE.with(|forms| {
+        for form in forms {
+            if let Err(err) = evaluate(form.clone(), root.clone()) {
+                panic!("prelude.mal failed on {}: {}", form, err);
+            }
+        }
+    });
+    env_new(Some(root))
+}
+
+/** Env for `user` like `env_core`, but without evaluating the prelude */
+pub fn env_bare() -> Env {
+    env_new(Some(env_natives()))
+}
+
+fn env_natives() -> Env {
     let env = env_new(None);
     {
         let mut env = env.borrow_mut();
-        let mut set = |sym, func| env.set(sym, Sexp::Func(func));
-        set("+", add);
-        set("-", subtract);
-        set("*", multiply);
-        set("/", divide);
-        set("prn", prn);
-        set("println", println);
-        set("pr-str", pr_str);
-        set("str", str);
-        set("list", list);
-        set("list?", is_list);
-        set("empty?", is_empty);
-        set("count", count);
-        set("=", eq);
-        set("<", lt);
-        set(">", gt);
-        set("<=", le);
-        set(">=", ge);
+        let mut set =
+            |sym, arity, func: Func| env.set(sym, Sexp::Func(Native::new(sym, arity, func)));
+        set("+", Arity::at_least(0), add);
+        set("-", Arity::at_least(1), subtract);
+        set("*", Arity::at_least(0), multiply);
+        set("/", Arity::at_least(1), divide);
+        set("quot", Arity::exactly(2), quot);
+        set("rem", Arity::exactly(2), rem);
+        set("mod", Arity::exactly(2), modulo);
+        set("double", Arity::exactly(1), double);
+        set("int", Arity::exactly(1), int);
+        set("floor", Arity::exactly(1), floor);
+        set("ceil", Arity::exactly(1), ceil);
+        set("round", Arity::exactly(1), round);
+        set("sqrt", Arity::exactly(1), sqrt);
+        set("numerator", Arity::exactly(1), numerator);
+        set("denominator", Arity::exactly(1), denominator);
+        set("abs", Arity::exactly(1), abs);
+        set("min", Arity::at_least(1), min);
+        set("max", Arity::at_least(1), max);
+        set("pow", Arity::exactly(2), pow);
+        set("inc", Arity::exactly(1), inc);
+        set("dec", Arity::exactly(1), dec);
+        set("bit-and", Arity::at_least(2), bit_and);
+        set("bit-or", Arity::at_least(2), bit_or);
+        set("bit-xor", Arity::at_least(2), bit_xor);
+        set("bit-shift-left", Arity::exactly(2), bit_shift_left);
+        set("bit-shift-right", Arity::exactly(2), bit_shift_right);
+        set("even?", Arity::exactly(1), is_even);
+        set("odd?", Arity::exactly(1), is_odd);
+        set("zero?", Arity::exactly(1), is_zero);
+        set("pos?", Arity::exactly(1), is_pos);
+        set("neg?", Arity::exactly(1), is_neg);
+        set("rand", Arity::between(0, 1), rand);
+        set("rand-int", Arity::exactly(1), rand_int);
+        set("rand-nth", Arity::exactly(1), rand_nth);
+        set("shuffle", Arity::exactly(1), shuffle);
+        set("rand-seed!", Arity::exactly(1), rand_seed);
+        set("prn", Arity::at_least(0), prn);
+        set("println", Arity::at_least(0), println);
+        set("pr-str", Arity::at_least(0), pr_str);
+        set("str", Arity::at_least(0), str);
+        set("list", Arity::at_least(0), list);
+        set("list?", Arity::exactly(1), is_list);
+        set("vector", Arity::at_least(0), vector);
+        set("hash-map", Arity::at_least(0), hash_map);
+        set("first", Arity::exactly(1), first);
+        set("rest", Arity::exactly(1), rest);
+        set("cons", Arity::exactly(2), cons);
+        set("conj", Arity::at_least(1), conj);
+        set("nth", Arity::exactly(2), nth);
+        set("assoc", Arity::at_least(1), assoc);
+        set("dissoc", Arity::at_least(1), dissoc);
+        set("get", Arity::between(2, 3), get);
+        set("empty?", Arity::exactly(1), is_empty);
+        set("count", Arity::exactly(1), count);
+        set("=", Arity::at_least(1), eq);
+        set("==", Arity::at_least(1), num_eq);
+        set("<", Arity::at_least(1), lt);
+        set(">", Arity::at_least(1), gt);
+        set("<=", Arity::at_least(1), le);
+        set(">=", Arity::at_least(1), ge);
+        set("map", Arity::exactly(2), map);
+        set("filter", Arity::exactly(2), filter);
+        set("reduce", Arity::between(2, 3), reduce);
+        set("seq", Arity::exactly(1), seq_native);
+        set("range", Arity::between(0, 3), range);
+        set("iterate", Arity::exactly(2), iterate);
+        set("take", Arity::exactly(2), take);
+        set("repeat", Arity::between(1, 2), repeat);
+        set("every?", Arity::exactly(2), is_every);
+        set("some", Arity::exactly(2), some);
+        set("apply", Arity::at_least(2), apply_native);
+        set("sort", Arity::exactly(1), sort);
+        set("sort-by", Arity::exactly(2), sort_by);
+        set("throw", Arity::exactly(1), throw);
+        set("json-read-str", Arity::between(1, 2), json_read_str);
+        set("json-write-str", Arity::exactly(1), json_write_str);
+        set("in-ns", Arity::exactly(1), in_ns);
+        set("require", Arity::at_least(1), require);
     }
     env
 }
 
 macro_rules! arithmetic_op {
+    ($func:ident, $name:literal, $ops:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            match args {
+                [x, y] => number::arithmetic($name, x, y, &$ops),
+                _ => Err(format!(
+                    "{} expected 2 numbers, received [{}]",
+                    $name,
+                    to_str(args)
+                )),
+            }
+        }
+    };
+}
+
+/**
+ * Folds `ops` left to right over any number of args. With a single arg the
+ * `identity` is used as the left operand, so `(- 5)` is `-5` and `(/ 2)` is
+ * `1/2`. No args returns `identity` when `nullary` is set, otherwise errors.
+ */
+macro_rules! variadic_op {
+    ($func:ident, $name:literal, $ops:expr, $identity:expr, $nullary:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            let name = $name;
+            match args {
+                [] if $nullary => Ok(Sexp::Integer($identity)),
+                [] => Err(format!("{} expected at least 1 arg, received none", name)),
+                [x] => number::arithmetic(name, &Sexp::Integer($identity), x, &$ops),
+                [x, rest @ ..] => rest
+                    .iter()
+                    .try_fold(x.clone(), |acc, y| number::arithmetic(name, &acc, y, &$ops)),
+            }
+        }
+    };
+}
+
+variadic_op!(add, "+", number::ADD, 0, true);
+variadic_op!(subtract, "-", number::SUBTRACT, 0, false);
+variadic_op!(multiply, "*", number::MULTIPLY, 1, true);
+variadic_op!(divide, "/", number::DIVIDE, 1, false);
+arithmetic_op!(quot, "quot", number::QUOT);
+arithmetic_op!(rem, "rem", number::REM);
+arithmetic_op!(modulo, "mod", number::MOD);
+
+macro_rules! rounding_op {
+    ($func:ident, $op:expr, $ratio_op:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            match args {
+                [Sexp::Float(Float(x))] => Ok(Sexp::Float(Float($op(*x)))),
+                [Sexp::Ratio(ratio)] => Ok(number::normalize($ratio_op(ratio).to_integer())),
+                [num @ (Sexp::Integer(_) | Sexp::BigInt(_))] => Ok(num.clone()),
+                _ => Err(format!(
+                    "{} expected 1 number, received [{}]",
+                    stringify!($func),
+                    to_str(args)
+                )),
+            }
+        }
+    };
+}
+
+rounding_op!(floor, f64::floor, BigRational::floor);
+rounding_op!(ceil, f64::ceil, BigRational::ceil);
+rounding_op!(round, f64::round, BigRational::round);
+
+fn sqrt(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let Sexp::Float(Float(x)) = double(args, env)? else {
+        return Err("sqrt failed unexpectedly".to_string());
+    };
+    Ok(Sexp::Float(Float(x.sqrt())))
+}
+
+fn double(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [num] => number::to_f64(num),
+        _ => None,
+    }
+    .map(|x| Sexp::Float(Float(x)))
+    .ok_or_else(|| format!("double expected 1 number, received [{}]", to_str(args)))
+}
+
+fn int(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Float(Float(x))] => number::float_to_int(*x)
+            .ok_or_else(|| format!("int cannot convert {} to an integer", Float(*x))),
+        [Sexp::Ratio(ratio)] => Ok(number::normalize(ratio.to_integer())),
+        [num @ (Sexp::Integer(_) | Sexp::BigInt(_))] => Ok(num.clone()),
+        _ => Err(format!(
+            "int expected 1 number, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+fn numerator(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Ratio(ratio)] => Ok(number::normalize(ratio.numer().clone())),
+        [num @ (Sexp::Integer(_) | Sexp::BigInt(_))] => Ok(num.clone()),
+        _ => Err(format!(
+            "numerator expected 1 rational, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+fn denominator(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Ratio(ratio)] => Ok(number::normalize(ratio.denom().clone())),
+        [Sexp::Integer(_) | Sexp::BigInt(_)] => Ok(Sexp::Integer(1)),
+        _ => Err(format!(
+            "denominator expected 1 rational, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+fn abs(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [num] if number::compare(num, &Sexp::Integer(0)) == Some(Ordering::Less) => {
+            number::arithmetic("abs", &Sexp::Integer(0), num, &number::SUBTRACT)
+        }
+        [num] if number::is_number(num) => Ok(num.clone()),
+        _ => Err(format!(
+            "abs expected 1 number, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+macro_rules! extremum {
+    ($func:ident, $ord:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            if args.is_empty() || !args.iter().all(number::is_number) {
+                return Err(format!(
+                    "{} expected at least 1 number, received [{}]",
+                    stringify!($func),
+                    to_str(args)
+                ));
+            }
+            let is_nan = |num: &Sexp| number::to_f64(num).is_some_and(f64::is_nan);
+            Ok(args
+                .iter()
+                .skip(1)
+                .fold(&args[0], |acc, num| match number::compare(num, acc) {
+                    _ if is_nan(acc) => acc,
+                    Some(ord) if ord == $ord => num,
+                    None => num,
+                    _ => acc,
+                })
+                .clone())
+        }
+    };
+}
+
+extremum!(min, Ordering::Less);
+extremum!(max, Ordering::Greater);
+
+fn pow(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [base, exp] => number::pow(base, exp),
+        _ => Err(format!(
+            "pow expected 2 numbers, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+fn inc(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [num] if number::is_number(num) => {
+            number::arithmetic("inc", num, &Sexp::Integer(1), &number::ADD)
+        }
+        _ => Err(format!(
+            "inc expected 1 number, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+fn dec(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [num] if number::is_number(num) => {
+            number::arithmetic("dec", num, &Sexp::Integer(1), &number::SUBTRACT)
+        }
+        _ => Err(format!(
+            "dec expected 1 number, received [{}]",
+            to_str(args)
+        )),
+    }
+}
+
+macro_rules! bit_op {
     ($func:ident, $op:tt) => {
-        fn $func(args: &[Sexp]) -> Result<Sexp, String> {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
             match args {
-                [Sexp::Integer(x), Sexp::Integer(y)] => Ok(Sexp::Integer(x $op y)),
-                _ => Err(format!("{}() received unexpected inputs: [{}]", stringify!($func), to_str(args)))
+                [first, rest @ ..] if !rest.is_empty() => rest
+                    .iter()
+                    .try_fold(first.clone(), |acc, num| match (&acc, num) {
+                        (Sexp::Integer(x), Sexp::Integer(y)) => Some(Sexp::Integer(x $op y)),
+                        _ => Some(number::normalize(number::to_big(&acc)? $op number::to_big(num)?)),
+                    }),
+                _ => None,
             }
+            .ok_or_else(|| format!(
+                "{} expected at least 2 integers, received [{}]",
+                stringify!($func),
+                to_str(args)
+            ))
         }
     };
 }
 
-arithmetic_op!(add, +);
-arithmetic_op!(subtract, -);
-arithmetic_op!(multiply, *);
-arithmetic_op!(divide, /);
+bit_op!(bit_and, &);
+bit_op!(bit_or, |);
+bit_op!(bit_xor, ^);
 
+macro_rules! shift_op {
+    ($func:ident, $shift:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            match args {
+                [num @ (Sexp::Integer(_) | Sexp::BigInt(_)), Sexp::Integer(n)] => {
+                    let n = u32::try_from(*n).map_err(|_| {
+                        format!(
+                            "{} expected an integer and a shift in [0, 2^32), received [{}]",
+                            stringify!($func),
+                            to_str(args)
+                        )
+                    })?;
+                    $shift(num, n)
+                }
+                _ => Err(format!(
+                    "{} expected an integer and a shift, received [{}]",
+                    stringify!($func),
+                    to_str(args)
+                )),
+            }
+        }
+    };
+}
+
+shift_op!(bit_shift_left, number::shift_left);
+shift_op!(bit_shift_right, number::shift_right);
+
+macro_rules! parity {
+    ($func:ident, $rem:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            match args {
+                [num @ (Sexp::Integer(_) | Sexp::BigInt(_))] => Ok(Sexp::Bool(
+                    number::arithmetic(stringify!($func), num, &Sexp::Integer(2), &number::MOD)?
+                        == Sexp::Integer($rem),
+                )),
+                _ => Err(format!(
+                    "{} expected 1 integer, received [{}]",
+                    stringify!($func),
+                    to_str(args)
+                )),
+            }
+        }
+    };
+}
+
+parity!(is_even, 0);
+parity!(is_odd, 1);
+
+macro_rules! sign {
+    ($func:ident, $ord:expr) => {
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            match args {
+                [num] if number::is_number(num) => Ok(Sexp::Bool(
+                    number::compare(num, &Sexp::Integer(0)) == Some($ord),
+                )),
+                _ => Err(format!(
+                    "{} expected 1 number, received [{}]",
+                    stringify!($func),
+                    to_str(args)
+                )),
+            }
+        }
+    };
+}
+
+sign!(is_zero, Ordering::Equal);
+sign!(is_pos, Ordering::Greater);
+sign!(is_neg, Ordering::Less);
+
+/**
+ * Checks `$op` holds between every adjacent pair of numbers by value,
+ * `(< 1 2.5 3)`. Other values are ordered as `=` compares them.
+ */
 macro_rules! cmp {
     ($func:ident, $op:tt) => {
-        fn $func(args: &[Sexp]) -> Result<Sexp, String> {
-            match args {
-                [sexp_l, sexp_r] => Ok(Sexp::Bool(sexp_l $op sexp_r)),
-                _ => Err(format!("{} expects 2 args, received {}", stringify!($op), to_str(args))),
+        fn $func(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+            if args.is_empty() {
+                return Err(format!("{} expected at least 1 arg, received none", stringify!($op)));
+            }
+            for pair in args.windows(2) {
+                let holds = match pair {
+                    [sexp_l, sexp_r] if number::is_number(sexp_l) && number::is_number(sexp_r) => {
+                        number::compare(sexp_l, sexp_r).is_some_and(|ord| ord $op Ordering::Equal)
+                    }
+                    [sexp_l, sexp_r] => lazy::compare(sexp_l, sexp_r)? $op Ordering::Equal,
+                    _ => unreachable!("windows(2) always yields pairs"),
+                };
+                if !holds {
+                    return Ok(Sexp::Bool(false));
+                }
             }
+            Ok(Sexp::Bool(true))
         }
     };
 }
 
-cmp!(eq, ==);
+cmp!(num_eq, ==);
 cmp!(lt, <);
 cmp!(le, <=);
 cmp!(gt, >);
 cmp!(ge, >=);
 
-fn prn(args: &[Sexp]) -> Result<Sexp, String> {
-    let Sexp::String(s) = pr_str(args)? else {
+/**
+ * Structural equality, the same inside collections as outside: `1` and `1.0`
+ * differ while `==` compares them by value. Lazy seqs compare equal to lists
+ * of the same elements, realised only as far as needed.
+ */
+fn eq(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    for pair in args.windows(2) {
+        if lazy::compare(&pair[0], &pair[1])? != Ordering::Equal {
+            return Ok(Sexp::Bool(false));
+        }
+    }
+    Ok(Sexp::Bool(true))
+}
+
+fn rand(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let scale = match args {
+        [] => 1.0,
+        [num] if number::is_number(num) => number::to_f64(num).unwrap_or(f64::NAN),
+        _ => {
+            return Err(format!(
+                "rand expected 0 or 1 number, received {}",
+                to_str(args)
+            ))
+        }
+    };
+    Ok(Sexp::Float(Float(with_rng(env, Rng::next_f64) * scale)))
+}
+
+fn rand_int(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Integer(n)] if *n > 0 => Ok(Sexp::Integer(
+            with_rng(env, |rng| rng.below(*n as u64)) as i64,
+        )),
+        _ => Err(format!(
+            "rand-int expected 1 positive Integer, received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn rand_nth(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::List(list) | Sexp::Vec(list)] if !list.is_empty() => {
+            let index = with_rng(env, |rng| rng.below(list.len() as u64));
+            Ok(list[index as usize].clone())
+        }
+        _ => Err(format!(
+            "rand-nth expected 1 non-empty List or Vec, received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn shuffle(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [Sexp::List(list) | Sexp::Vec(list)] = args else {
+        return Err(format!(
+            "shuffle expected 1 List or Vec, received {}",
+            to_str(args)
+        ));
+    };
+    let mut list = list.iter().cloned().collect::<Vec<Sexp>>();
+    with_rng(env, |rng| {
+        for i in (1..list.len()).rev() {
+            list.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+    });
+    Ok(Sexp::Vec(list.into()))
+}
+
+fn rand_seed(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [Sexp::Integer(seed)] = args else {
+        return Err(format!(
+            "rand-seed! expected 1 Integer, received {}",
+            to_str(args)
+        ));
+    };
+    with_rng(env, |rng| *rng = Rng::new(*seed as u64));
+    Ok(Sexp::Nil)
+}
+
+fn prn(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let Sexp::String(s) = pr_str(args, env)? else {
         return Err("prn failed unexpectedly".to_string());
     };
     println!("{}", s);
     Ok(Sexp::Nil)
 }
 
-fn println(args: &[Sexp]) -> Result<Sexp, String> {
-    let Sexp::String(s) = str(args)? else {
+fn println(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let Sexp::String(s) = str(args, env)? else {
         return Err("println failed unexpectedly".to_string());
     };
     println!("{}", s);
     Ok(Sexp::Nil)
 }
 
-fn pr_str(args: &[Sexp]) -> Result<Sexp, String> {
+fn pr_str(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    args.iter().try_for_each(lazy::realise)?;
     Ok(Sexp::String(to_str(args)))
 }
 
-fn str(args: &[Sexp]) -> Result<Sexp, String> {
+fn str(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    args.iter().try_for_each(lazy::realise)?;
     Ok(Sexp::String(
         args.iter()
             .map(|s| match s {
@@ -92,30 +559,493 @@ fn str(args: &[Sexp]) -> Result<Sexp, String> {
     ))
 }
 
-fn list(args: &[Sexp]) -> Result<Sexp, String> {
-    Ok(Sexp::List(args.to_vec()))
+fn list(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    Ok(Sexp::List(Vector::from(args)))
 }
 
-fn is_list(args: &[Sexp]) -> Result<Sexp, String> {
+fn is_list(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
     Ok(Sexp::Bool(matches!(args, [Sexp::List(_)])))
 }
 
-fn is_empty(args: &[Sexp]) -> Result<Sexp, String> {
-    Ok(Sexp::Bool(matches!(args, [Sexp::List(l)] if l.is_empty())))
+fn vector(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    Ok(Sexp::Vec(Vector::from(args)))
+}
+
+fn hash_map(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    assoc(&[&[Sexp::Map(Map::default())], args].concat(), env)
+}
+
+fn is_empty(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    Ok(Sexp::Bool(match args {
+        [Sexp::List(list) | Sexp::Vec(list)] => list.is_empty(),
+        [Sexp::Map(map)] => map.is_empty(),
+        [Sexp::Set(set)] => set.is_empty(),
+        [Sexp::Lazy(seq)] => seq.step()?.is_none(),
+        [Sexp::Nil] => true,
+        _ => false,
+    }))
+}
+
+fn count(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::List(list) | Sexp::Vec(list)] => Ok(Sexp::Integer(list.len() as i64)),
+        [Sexp::Map(map)] => Ok(Sexp::Integer(map.len() as i64)),
+        [Sexp::Set(set)] => Ok(Sexp::Integer(set.len() as i64)),
+        [lazy @ Sexp::Lazy(_)] => lazy::iter(lazy)
+            .try_fold(0, |n, sexp| sexp.map(|_| n + 1))
+            .map(Sexp::Integer),
+        [Sexp::Nil] => Ok(Sexp::Integer(0)),
+        _ => Err(format!("count expected 1 List, received {}", to_str(args))),
+    }
+}
+
+fn first(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::List(list) | Sexp::Vec(list)] => Ok(list.front().cloned().unwrap_or(Sexp::Nil)),
+        [Sexp::Lazy(seq)] => Ok(seq.step()?.map_or(Sexp::Nil, |(head, _)| head)),
+        [Sexp::Nil] => Ok(Sexp::Nil),
+        _ => Err(format!(
+            "first expected 1 List or Vec, received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn rest(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::List(list) | Sexp::Vec(list)] if !list.is_empty() => Ok(Sexp::List(list.skip(1))),
+        [Sexp::Lazy(seq)] => Ok(seq
+            .step()?
+            .map_or(Sexp::List(Vector::new()), |(_, tail)| tail)),
+        [Sexp::List(_) | Sexp::Vec(_) | Sexp::Nil] => Ok(Sexp::List(Vector::new())),
+        _ => Err(format!(
+            "rest expected 1 List or Vec, received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn cons(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    let (sexp, mut list) = match args {
+        [sexp, Sexp::List(list) | Sexp::Vec(list)] => (sexp, list.clone()),
+        [sexp, tail @ Sexp::Lazy(_)] => {
+            return Ok(Sexp::Lazy(LazySeq::cons(sexp.clone(), tail.clone())))
+        }
+        [sexp, Sexp::Nil] => (sexp, Vector::new()),
+        _ => {
+            return Err(format!(
+                "cons expected [Sexp, List], received {}",
+                to_str(args)
+            ))
+        }
+    };
+    list.push_front(sexp.clone());
+    Ok(Sexp::List(list))
 }
 
-fn count(args: &[Sexp]) -> Result<Sexp, String> {
+/** Adds to the front of lists, the back of vectors, and `[key val]` pairs to maps */
+fn conj(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
     match args {
-        [Sexp::List(list)] => Ok(Sexp::Integer(list.len() as i64)),
-        _ => Err(format!("count expected 1 List, recieved {}", to_str(args))),
+        [Sexp::List(list), sexps @ ..] => {
+            let mut list = list.clone();
+            sexps.iter().for_each(|sexp| list.push_front(sexp.clone()));
+            Ok(Sexp::List(list))
+        }
+        [Sexp::Nil, sexps @ ..] => Ok(Sexp::List(sexps.iter().rev().cloned().collect())),
+        [Sexp::Vec(list), sexps @ ..] => {
+            let mut list = list.clone();
+            list.extend(sexps.iter().cloned());
+            Ok(Sexp::Vec(list))
+        }
+        [Sexp::Map(map), pairs @ ..] => {
+            let mut map = map.clone();
+            for pair in pairs {
+                match pair {
+                    Sexp::Vec(pair) if pair.len() == 2 => {
+                        map.insert(pair[0].clone(), pair[1].clone());
+                    }
+                    _ => {
+                        return Err(format!(
+                            "conj expected [Key Val] for Map, received {}",
+                            pair
+                        ))
+                    }
+                }
+            }
+            Ok(Sexp::Map(map))
+        }
+        _ => Err(format!(
+            "conj expected a collection, received {}",
+            to_str(args)
+        )),
     }
 }
 
+fn nth(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::List(list) | Sexp::Vec(list), Sexp::Integer(index)] => usize::try_from(*index)
+            .ok()
+            .and_then(|index| list.get(index))
+            .cloned()
+            .ok_or_else(|| format!("nth index {} out of range for {}", index, args[0])),
+        [lazy @ Sexp::Lazy(_), Sexp::Integer(index)] => usize::try_from(*index)
+            .ok()
+            .and_then(|index| lazy::iter(lazy).nth(index))
+            .unwrap_or_else(|| Err(format!("nth index {} out of range for {}", index, lazy))),
+        _ => Err(format!(
+            "nth expected [List, Integer], received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn assoc(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [_, pairs @ ..] if pairs.len() % 2 == 1 => Err(format!(
+            "assoc expected an even number of keys and values, received {}",
+            to_str(pairs)
+        )),
+        [Sexp::Map(map), pairs @ ..] => {
+            let mut map = map.clone();
+            for pair in pairs.chunks_exact(2) {
+                map.insert(pair[0].clone(), pair[1].clone());
+            }
+            Ok(Sexp::Map(map))
+        }
+        [Sexp::Vec(list), pairs @ ..] => {
+            let mut list = list.clone();
+            for pair in pairs.chunks_exact(2) {
+                let index = match &pair[0] {
+                    Sexp::Integer(index) => usize::try_from(*index).ok(),
+                    _ => None,
+                };
+                match index {
+                    Some(index) if index < list.len() => {
+                        list.set(index, pair[1].clone());
+                    }
+                    Some(index) if index == list.len() => list.push_back(pair[1].clone()),
+                    _ => return Err(format!("assoc index {} out of range for Vec", pair[0])),
+                }
+            }
+            Ok(Sexp::Vec(list))
+        }
+        _ => Err(format!(
+            "assoc expected a Map or Vec, received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn dissoc(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Map(map), keys @ ..] => {
+            let mut map = map.clone();
+            keys.iter().for_each(|key| {
+                map.remove(key);
+            });
+            Ok(Sexp::Map(map))
+        }
+        _ => Err(format!("dissoc expected a Map, received {}", to_str(args))),
+    }
+}
+
+fn get(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    let (coll, key, default) = match args {
+        [coll, key] => (coll, key, Sexp::Nil),
+        [coll, key, default] => (coll, key, default.clone()),
+        _ => {
+            return Err(format!(
+                "get expected [Coll, Key], received {}",
+                to_str(args)
+            ))
+        }
+    };
+    let found = match (coll, key) {
+        (Sexp::Map(map), key) => map.get(key),
+        (Sexp::Vec(list), Sexp::Integer(index)) => usize::try_from(*index)
+            .ok()
+            .and_then(|index| list.get(index)),
+        _ => None,
+    };
+    Ok(found.cloned().unwrap_or(default))
+}
+
+/** Elements of a List, Vec or nil, for natives that walk a sequence */
+fn seq(name: &str, sexp: &Sexp) -> Result<Vector<Sexp>, String> {
+    match sexp {
+        Sexp::List(list) | Sexp::Vec(list) => Ok(list.clone()),
+        Sexp::Set(set) => Ok(set.iter().cloned().collect()),
+        Sexp::Lazy(_) => lazy::iter(sexp).collect(),
+        Sexp::Nil => Ok(Vector::new()),
+        _ => Err(format!(
+            "{} expected a List or Vec, received {}",
+            name, sexp
+        )),
+    }
+}
+
+/** `nil` for an empty collection, otherwise its elements as a List or lazy seq */
+fn seq_native(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Lazy(seq)] => Ok(match seq.step()? {
+            Some(_) => args[0].clone(),
+            None => Sexp::Nil,
+        }),
+        [coll] => {
+            let list = seq("seq", coll)?;
+            Ok(match list.is_empty() {
+                true => Sexp::Nil,
+                false => Sexp::List(list),
+            })
+        }
+        _ => Err(format!("seq expected 1 Coll, received {}", to_str(args))),
+    }
+}
+
+fn range(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    if let Some(arg) = args.iter().find(|arg| !number::is_number(arg)) {
+        return Err(format!("range expected numbers, received {}", arg));
+    }
+    let (start, end, step) = match args {
+        [] => (Sexp::Integer(0), None, Sexp::Integer(1)),
+        [end] => (Sexp::Integer(0), Some(end.clone()), Sexp::Integer(1)),
+        [start, end] => (start.clone(), Some(end.clone()), Sexp::Integer(1)),
+        [start, end, step] => (start.clone(), Some(end.clone()), step.clone()),
+        _ => unreachable!("arity is checked by Native::call"),
+    };
+    lazy::range(start, end, step)
+}
+
+fn iterate(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    match args {
+        [func, x] => Ok(lazy::iterate(func.clone(), x.clone(), env)),
+        _ => Err(format!(
+            "iterate expected [Func, Sexp], received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn take(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Integer(n), coll] => Ok(lazy::take(usize::try_from(*n).unwrap_or(0), coll.clone())),
+        _ => Err(format!(
+            "take expected [Integer, Coll], received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn repeat(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    match args {
+        [x] => Ok(lazy::repeat(x.clone())),
+        [Sexp::Integer(n), x] => Ok(lazy::take(
+            usize::try_from(*n).unwrap_or(0),
+            lazy::repeat(x.clone()),
+        )),
+        _ => Err(format!(
+            "repeat expected [Integer?, Sexp], received {}",
+            to_str(args)
+        )),
+    }
+}
+
+fn map(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [func, coll] = args else {
+        return Err(format!(
+            "map expected [Func, Coll], received {}",
+            to_str(args)
+        ));
+    };
+    if let Sexp::Lazy(_) = coll {
+        return Ok(lazy::map(func.clone(), coll.clone(), env));
+    }
+    seq("map", coll)?
+        .into_iter()
+        .map(|sexp| apply(func, vec![sexp], env))
+        .collect::<Result<Vector<Sexp>, _>>()
+        .map(Sexp::List)
+}
+
+fn filter(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [pred, coll] = args else {
+        return Err(format!(
+            "filter expected [Pred, Coll], received {}",
+            to_str(args)
+        ));
+    };
+    if let Sexp::Lazy(_) = coll {
+        return Ok(lazy::filter(pred.clone(), coll.clone(), env));
+    }
+    let mut kept = Vector::new();
+    for sexp in seq("filter", coll)? {
+        if truthy(&apply(pred, vec![sexp.clone()], env)?) {
+            kept.push_back(sexp);
+        }
+    }
+    Ok(Sexp::List(kept))
+}
+
+fn reduce(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let (func, init, coll) = match args {
+        [func, coll] => {
+            let mut coll = seq("reduce", coll)?;
+            match coll.pop_front() {
+                Some(init) => (func, init, coll),
+                None => return apply(func, vec![], env),
+            }
+        }
+        [func, init, coll] => (func, init.clone(), seq("reduce", coll)?),
+        _ => {
+            return Err(format!(
+                "reduce expected [Func, Init?, Coll], received {}",
+                to_str(args)
+            ))
+        }
+    };
+    coll.into_iter()
+        .try_fold(init, |acc, sexp| apply(func, vec![acc, sexp], env))
+}
+
+fn is_every(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [pred, coll] = args else {
+        return Err(format!(
+            "every? expected [Pred, Coll], received {}",
+            to_str(args)
+        ));
+    };
+    for sexp in seq("every?", coll)? {
+        if !truthy(&apply(pred, vec![sexp], env)?) {
+            return Ok(Sexp::Bool(false));
+        }
+    }
+    Ok(Sexp::Bool(true))
+}
+
+fn some(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [pred, coll] = args else {
+        return Err(format!(
+            "some expected [Pred, Coll], received {}",
+            to_str(args)
+        ));
+    };
+    for sexp in seq("some", coll)? {
+        let result = apply(pred, vec![sexp], env)?;
+        if truthy(&result) {
+            return Ok(result);
+        }
+    }
+    Ok(Sexp::Nil)
+}
+
+fn apply_native(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [func, middle @ .., last] = args else {
+        return Err(format!(
+            "apply expected [Func, Args*, Coll], received {}",
+            to_str(args)
+        ));
+    };
+    let mut args = middle.to_vec();
+    args.extend(seq("apply", last)?);
+    apply(func, args, env)
+}
+
+/**
+ * Numbers by value across types and before anything else, which follows in
+ * the structural order
+ */
+fn compare(x: &Sexp, y: &Sexp) -> Ordering {
+    match (number::is_number(x), number::is_number(y)) {
+        (true, true) => number::total_compare(x, y),
+        (x_number, y_number) => y_number.cmp(&x_number).then_with(|| x.cmp(y)),
+    }
+}
+
+fn sort(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    let [coll] = args else {
+        return Err(format!("sort expected 1 Coll, received {}", to_str(args)));
+    };
+    let mut list = seq("sort", coll)?.into_iter().collect::<Vec<Sexp>>();
+    list.sort_by(compare);
+    Ok(Sexp::List(list.into()))
+}
+
+fn sort_by(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [keyfn, coll] = args else {
+        return Err(format!(
+            "sort-by expected [KeyFn, Coll], received {}",
+            to_str(args)
+        ));
+    };
+    let mut keyed = seq("sort-by", coll)?
+        .into_iter()
+        .map(|sexp| Ok((apply(keyfn, vec![sexp.clone()], env)?, sexp)))
+        .collect::<Result<Vec<(Sexp, Sexp)>, String>>()?;
+    keyed.sort_by(|(x, _), (y, _)| compare(x, y));
+    Ok(Sexp::List(
+        keyed.into_iter().map(|(_, sexp)| sexp).collect(),
+    ))
+}
+
+/** Raises `sexp` as an error, which `try*` catches as the same value */
+fn throw(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    match args {
+        [sexp] => Err(raise(env, sexp.clone())),
+        _ => Err(format!("throw expected 1 arg, received {}", to_str(args))),
+    }
+}
+
+/** `(json-read-str s :string)` keeps object keys as strings instead of keywords */
+fn json_read_str(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    let (src, keys) = match args {
+        [Sexp::String(src)] => (src, json::Keys::Keyword),
+        [Sexp::String(src), Sexp::Keyword(keys)] if keys.as_str() == "keyword" => {
+            (src, json::Keys::Keyword)
+        }
+        [Sexp::String(src), Sexp::Keyword(keys)] if keys.as_str() == "string" => {
+            (src, json::Keys::String)
+        }
+        _ => {
+            return Err(format!(
+                "json-read-str expected String and optionally :keyword or :string, received {}",
+                to_str(args)
+            ))
+        }
+    };
+    json::read_str(src, keys)
+}
+
+fn json_write_str(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+    let [sexp] = args else {
+        return Err(format!(
+            "json-write-str expected 1 arg, received {}",
+            to_str(args)
+        ));
+    };
+    json::write_str(sexp).map(Sexp::String)
+}
+
+fn in_ns(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    let [Sexp::Symbol(ns)] = args else {
+        return Err(format!(
+            "in-ns expected 1 Symbol, received {}",
+            to_str(args)
+        ));
+    };
+    namespace::enter(env, *ns);
+    Ok(Sexp::Nil)
+}
+
+fn require(args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+    for spec in args {
+        namespace::require(env, spec)?;
+    }
+    Ok(Sexp::Nil)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::env::evaluate;
-    use crate::tokenizer::Tokenizer;
     use crate::types::Sexp;
 
     fn test_eq(test: &str, expect: &str) {
@@ -135,17 +1065,479 @@ mod tests {
         test_eq("()", "()");
     }
 
+    #[test]
+    fn test_variadic_arithmetic() {
+        test_eq("(+)", "0");
+        test_eq("(*)", "1");
+        test_eq("(+ 1)", "1");
+        test_eq("(* 2)", "2");
+        test_eq("(- 5)", "-5");
+        test_eq("(- 1.5)", "-1.5");
+        test_eq("(/ 2)", "1/2");
+        test_eq("(/ 0.5)", "2.0");
+        test_eq("(+ 1 2 3)", "6");
+        test_eq("(- 10 1 2 3)", "4");
+        test_eq("(* 1 2 3 4)", "24");
+        test_eq("(/ 60 2 3)", "10");
+        test_eq("(/ 1 2 3)", "1/6");
+        test_eq("(+ 9223372036854775807 1 -1)", "9223372036854775807");
+        test_eq("(- -9223372036854775808)", "9223372036854775808");
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        test_eq("(= 1)", "true");
+        test_eq("(< 1)", "true");
+        test_eq("(= 1 1 1)", "true");
+        test_eq("(= 1 1 2)", "false");
+        test_eq("(< 1 2 3)", "true");
+        test_eq("(< 1 3 2)", "false");
+        test_eq("(<= 1 1 2)", "true");
+        test_eq("(> 3 2 1)", "true");
+        test_eq("(>= 3 3 4)", "false");
+        test_eq("(< 1 3/2 2.5 9223372036854775808)", "true");
+    }
+
+    #[test]
+    fn test_integer_division() {
+        test_eq("(quot 7 2)", "3");
+        test_eq("(quot -7 2)", "-3");
+        test_eq("(rem 7 2)", "1");
+        test_eq("(rem -7 2)", "-1");
+        test_eq("(rem 7 -2)", "1");
+        test_eq("(mod 7 2)", "1");
+        test_eq("(mod -7 2)", "1");
+        test_eq("(mod 7 -2)", "-1");
+        test_eq("(mod -7 -2)", "-1");
+        test_eq("(mod -8 2)", "0");
+    }
+
+    #[test]
+    fn test_bigint_promotion() {
+        test_eq("(* 9223372036854775807 2)", "18446744073709551614");
+        test_eq("(+ 9223372036854775807 1)", "9223372036854775808");
+        test_eq("(- -9223372036854775807 2)", "-9223372036854775809");
+        test_eq("(/ -9223372036854775808 -1)", "9223372036854775808");
+        test_eq("(- (+ 9223372036854775807 1) 1)", "9223372036854775807");
+        test_eq(
+            "123456789012345678901234567890",
+            "123456789012345678901234567890",
+        );
+        test_eq(
+            "(* 123456789012345678901234567890 10)",
+            "1234567890123456789012345678900",
+        );
+        test_eq("(quot 100000000000000000000 3)", "33333333333333333333");
+        test_eq("(rem -100000000000000000000 3)", "-1");
+        test_eq("(mod -100000000000000000000 3)", "2");
+        test_eq("(= (+ 9223372036854775807 1) 9223372036854775808)", "true");
+        test_eq("(= (- 9223372036854775808 1) 9223372036854775807)", "true");
+        test_eq("(< 1 9223372036854775808)", "true");
+        test_eq("(< -9223372036854775809 1)", "true");
+        test_eq("(> 9223372036854775808 -9223372036854775809)", "true");
+    }
+
+    #[test]
+    fn test_floats() {
+        test_eq("1.5", "1.5");
+        test_eq("-2.", "-2.0");
+        test_eq("1e3", "1000.0");
+        test_eq("1.5E-7", "1.5e-7");
+        test_eq("1e100", "1e100");
+        test_eq("##NaN", "##NaN");
+        test_eq("##-Inf", "##-Inf");
+        test_eq("(+ 1 0.5)", "1.5");
+        test_eq("(* 0.5 9223372036854775808)", "4.611686018427388e18");
+        test_eq("(- 0.5 1)", "-0.5");
+        test_eq("(/ 1 2.0)", "0.5");
+        test_eq("(/ 1.0 0)", "##Inf");
+        test_eq("(/ -1 0.0)", "##-Inf");
+        test_eq("(quot 7.5 2)", "3.0");
+        test_eq("(rem -7.5 2)", "-1.5");
+        test_eq("(mod -7.5 2)", "0.5");
+        test_eq("(double 3)", "3.0");
+        test_eq("(int 3.9)", "3");
+        test_eq("(int -3.9)", "-3");
+        test_eq("(int 1e20)", "100000000000000000000");
+        test_eq("(floor -1.5)", "-2.0");
+        test_eq("(ceil 1.2)", "2.0");
+        test_eq("(round 2.5)", "3.0");
+        test_eq("(round 7)", "7");
+        test_eq("(sqrt 16)", "4.0");
+        test_eq("(= 1 1.0)", "false");
+        test_eq("(== 1 1.0 2/2)", "true");
+        test_eq("(= [1] [1.0])", "false");
+        test_eq("(= {:a 1} {:a 1.0})", "false");
+        test_eq("(get {1.0 :float} 1)", "nil");
+        test_eq("(= [0.0] [-0.0])", "true");
+        test_eq("(get {0.0 :zero} -0.0)", ":zero");
+        test_eq("(= [##NaN] [##NaN])", "true");
+        test_eq("(< 1 1.5)", "true");
+        test_eq("(>= 2.5 2)", "true");
+        test_eq("(= ##NaN ##NaN)", "true");
+        test_eq("(== ##NaN ##NaN)", "false");
+        test_eq("(< ##NaN 1)", "false");
+        test_eq("(= 0.0 -0.0)", "true");
+        test_eq("(< 1e300 ##Inf)", "true");
+    }
+
+    #[test]
+    fn test_ratios() {
+        test_eq("(/ 1 3)", "1/3");
+        test_eq("(/ -1 3)", "-1/3");
+        test_eq("(/ 1 -3)", "-1/3");
+        test_eq("(/ 2 4)", "1/2");
+        test_eq("(/ 100000000000000000000 3)", "100000000000000000000/3");
+        test_eq("2/4", "1/2");
+        test_eq("4/2", "2");
+        test_eq("-3/9", "-1/3");
+        test_eq("(+ 1/3 2/3)", "1");
+        test_eq("(+ 1/3 1)", "4/3");
+        test_eq("(- 1/2 1/3)", "1/6");
+        test_eq("(* 1/3 3)", "1");
+        test_eq("(/ 1/3 1/6)", "2");
+        test_eq("(/ 1/3 2)", "1/6");
+        test_eq("(+ 1/2 0.25)", "0.75");
+        test_eq("(quot 7/2 1)", "3");
+        test_eq("(rem -7/2 1)", "-1/2");
+        test_eq("(mod -7/2 1)", "1/2");
+        test_eq("(floor -7/2)", "-4");
+        test_eq("(ceil 7/2)", "4");
+        test_eq("(round 5/2)", "3");
+        test_eq("(int -7/2)", "-3");
+        test_eq("(double 1/4)", "0.25");
+        test_eq("(numerator 6/4)", "3");
+        test_eq("(denominator 6/4)", "2");
+        test_eq("(numerator 5)", "5");
+        test_eq("(denominator 5)", "1");
+        test_eq("(= 1/2 2/4)", "true");
+        test_eq("(= (/ 4 2) 2)", "true");
+        test_eq("(< 1/3 1/2)", "true");
+        test_eq("(< 1/3 1)", "true");
+        test_eq("(> 100000000000000000000/3 1)", "true");
+        test_eq("(< 1/3 0.5)", "true");
+    }
+
+    #[test]
+    fn test_math_library() {
+        test_eq("(abs -5)", "5");
+        test_eq("(abs 5)", "5");
+        test_eq("(abs -9223372036854775808)", "9223372036854775808");
+        test_eq("(abs -1/2)", "1/2");
+        test_eq("(abs -2.5)", "2.5");
+        test_eq("(min 3 1 2)", "1");
+        test_eq("(max 3 1 2)", "3");
+        test_eq("(max 1 5/2 2.0)", "5/2");
+        test_eq("(min 1 ##NaN 0)", "##NaN");
+        test_eq("(pow 2 10)", "1024");
+        test_eq("(pow 2 64)", "18446744073709551616");
+        test_eq("(pow 2 -2)", "1/4");
+        test_eq("(pow 2/3 2)", "4/9");
+        test_eq("(pow 4 0.5)", "2.0");
+        test_eq("(pow 2.0 3)", "8.0");
+        test_eq("(inc 1)", "2");
+        test_eq("(dec 1)", "0");
+        test_eq("(inc 9223372036854775807)", "9223372036854775808");
+        test_eq("(inc 1/2)", "3/2");
+        test_eq("(bit-and 12 10)", "8");
+        test_eq("(bit-or 12 10 1)", "15");
+        test_eq("(bit-xor 12 10)", "6");
+        test_eq("(bit-and -1 18446744073709551616)", "18446744073709551616");
+        test_eq("(bit-shift-left 1 10)", "1024");
+        test_eq("(bit-shift-left 1 64)", "18446744073709551616");
+        test_eq("(bit-shift-right 1024 3)", "128");
+        test_eq("(bit-shift-right -1 3)", "-1");
+        test_eq("(bit-shift-left 0 4000000000)", "0");
+        test_eq("(pow 1 2000000000)", "1");
+        test_eq("(pow -1/1 2000000001)", "-1");
+        test_eq("(= (pow 2 1000000) (bit-shift-left 1 1000000))", "true");
+        test_eq("(even? 2)", "true");
+        test_eq("(even? -3)", "false");
+        test_eq("(odd? -3)", "true");
+        test_eq("(odd? 18446744073709551617)", "true");
+        test_eq("(zero? 0)", "true");
+        test_eq("(zero? 0.0)", "true");
+        test_eq("(zero? 1/2)", "false");
+        test_eq("(pos? 1/2)", "true");
+        test_eq("(neg? -0.5)", "true");
+        test_eq("(neg? ##NaN)", "false");
+    }
+
+    #[test]
+    fn test_seeded_random() {
+        let seeded = |form: &str| format!("(do (rand-seed! 42) {})", form);
+        test_eq(
+            &seeded("(list (rand-int 100) (rand-int 100) (rand-int 100))"),
+            "(13 91 58)",
+        );
+        test_eq(&seeded("(rand-nth [:a :b :c :d])"), ":b");
+        test_eq(&seeded("(shuffle (list 1 2 3 4 5))"), "[2 3 1 5 4]");
+        test_eq(&seeded("(< 0 (rand) 1)"), "true");
+        test_eq(&seeded("(< 0 (rand 10) 10)"), "true");
+        test_eq("(shuffle [])", "[]");
+        test_eq("(rand-int 1)", "0");
+        test_eq("(rand-nth (list 7))", "7");
+    }
+
+    #[test]
+    fn test_collections() {
+        test_eq("(vector 1 2)", "[1 2]");
+        test_eq("(first (list 1 2 3))", "1");
+        test_eq("(first [])", "nil");
+        test_eq("(rest [1 2 3])", "(2 3)");
+        test_eq("(rest ())", "()");
+        test_eq("(rest nil)", "()");
+        test_eq("(cons 0 [1 2])", "(0 1 2)");
+        test_eq("(cons 0 nil)", "(0)");
+        test_eq("(conj (list 1 2) 3 4)", "(4 3 1 2)");
+        test_eq("(conj [1 2] 3 4)", "[1 2 3 4]");
+        test_eq("(conj {} [:a 1])", "{:a 1}");
+        test_eq("(conj nil 1 2)", "(2 1)");
+        test_eq("(nth [1 2 3] 2)", "3");
+        test_eq("(assoc [1 2] 2 3)", "[1 2 3]");
+        test_eq("(assoc [1 2] 0 :x)", "[:x 2]");
+        test_eq("(get (assoc {} :a 1 :b 2) :b)", "2");
+        test_eq("(count (assoc {:a 1} :a 2))", "1");
+        test_eq("(get (dissoc {:a 1 :b 2} :a) :a :none)", ":none");
+        test_eq("(= (hash-map :a 1 :b 2) {:b 2 :a 1})", "true");
+        test_eq("(get [1 2] 5)", "nil");
+        test_eq("(get nil :a)", "nil");
+        test_eq("(count {:a 1 :b 2})", "2");
+        test_eq("(count [1 2 3])", "3");
+        test_eq("(empty? [])", "true");
+        test_eq("(empty? {:a 1})", "false");
+    }
+
+    #[test]
+    fn test_higher_order() {
+        test_eq("(map inc [1 2 3])", "(2 3 4)");
+        test_eq("(map (fn* (x) (* x x)) (list 1 2))", "(1 4)");
+        test_eq("(let* (n 10) (map (fn* (x) (+ x n)) [1 2]))", "(11 12)");
+        test_eq("(map inc nil)", "()");
+        test_eq("(filter even? [1 2 3 4])", "(2 4)");
+        test_eq("(reduce + 0 [1 2 3])", "6");
+        test_eq("(reduce + [1 2 3])", "6");
+        test_eq("(reduce + [])", "0");
+        test_eq("(reduce (fn* (acc x) (cons x acc)) () [1 2])", "(2 1)");
+        test_eq("(every? even? [2 4])", "true");
+        test_eq("(every? even? [2 3])", "false");
+        test_eq("(some (fn* (x) (if (> x 1) (* x 10))) [1 2 3])", "20");
+        test_eq("(some even? [])", "nil");
+        test_eq("(apply + 1 2 [3 4])", "10");
+        test_eq("(apply list [])", "()");
+        test_eq("(sort [3 1.5 2 1/2])", "(1/2 1.5 2 3)");
+        test_eq("(sort [1 2.0 ##NaN 1/2])", "(1/2 1 2.0 ##NaN)");
+        test_eq(
+            "(sort [##NaN 3 ##-Inf ##NaN 1 ##Inf])",
+            "(##-Inf 1 3 ##Inf ##NaN ##NaN)",
+        );
+        test_eq("(sort [:b 2 \"a\" 1.5 :a])", "(1.5 2 :a :b \"a\")");
+        test_eq("(sort-by (fn* (x) (- x)) [1 3 2])", "(3 2 1)");
+        test_eq("(sort-by count [[1 2] [] [3]])", "([] [3] [1 2])");
+    }
+
+    #[test]
+    fn test_try() {
+        test_eq("(try* 1 (catch* e 2))", "1");
+        test_eq("(try* (throw \"boom\") (catch* e [e]))", "[\"boom\"]");
+        test_eq("(try* (throw {:a 1}) (catch* e e))", "{:a 1}");
+        test_eq("(try* (throw {:a 1}) (catch* e (get e :a)))", "1");
+        test_eq(
+            "(try* (abc) (catch* e e))",
+            "\"Unknown symbol 'abc' found\"",
+        );
+        test_eq("(try* (map (fn* (x) (throw x)) [7]) (catch* e e))", "7");
+        test_eq("(try* (count 1 2) (catch* e 0))", "0");
+        test_eq(
+            "(let* (a 1) (try* (let* (b 2) (throw b)) (catch* e [a e])))",
+            "[1 2]",
+        );
+        test_eq("(try* 5)", "5");
+    }
+
+    #[test]
+    fn test_json() {
+        test_eq(
+            "(json-read-str \"[1, 2.5, null, true, []]\")",
+            "[1 2.5 nil true []]",
+        );
+        test_eq(
+            "(json-write-str {:a [1 nil \"b\"]})",
+            r#""{"a":[1,null,"b"]}""#,
+        );
+        test_eq(
+            "(json-read-str (json-write-str {:a {\"b\" 1}}))",
+            "{:a {:b 1}}",
+        );
+        test_eq(
+            "(json-read-str (json-write-str {:a 1}) :string)",
+            r#"{"a" 1}"#,
+        );
+        test_eq(
+            "(try* (json-write-str [1 +]) (catch* e e))",
+            "\"json-write-str cannot represent Func <func>\"",
+        );
+        test_fail("(json-read-str \"[1\")");
+        test_fail("(json-read-str \"1\" :symbol)");
+        test_fail("(json-write-str {1 2})");
+    }
+
+    #[test]
+    fn test_closures() {
+        test_eq("(if nil 1 2)", "2");
+        test_eq("(if 0 1 2)", "1");
+        test_eq("(if false 1)", "nil");
+        test_eq("((fn* (a b) (+ a b)) 1 2)", "3");
+        test_eq("((fn* [& more] more) 1 2)", "(1 2)");
+        test_eq("((fn* (a & more) (count more)) 1)", "0");
+        test_eq("(((fn* (a) (fn* (b) (+ a b))) 5) 7)", "12");
+        test_eq("(let* (a 1 b (+ a 1) a 5) [a b])", "[5 2]");
+        test_eq("(let* (x 4) (let* (y 2) (do (def! x 5) x)))", "4");
+        test_eq("(do (def! a 4) (let* (x 4) (def! a 5)) a)", "5");
+        test_eq("(do ((fn* () (def! z 3))) z)", "3");
+        test_eq(
+            "(do (def! fib (fn* (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))) (fib 15))",
+            "610",
+        );
+        test_eq(
+            "(do (def! sumdown (fn* (n) (if (> n 0) (+ n (sumdown (- n 1))) 0))) (sumdown 100))",
+            "5050",
+        );
+    }
+
+    #[test]
+    fn test_loop() {
+        test_eq(
+            "(loop (i 0 acc 1) (if (< i 10) (recur (+ i 1) (* acc 2)) acc))",
+            "1024",
+        );
+        test_eq(
+            "(loop [i 3 out []] (if (zero? i) out (let* (j (dec i)) (recur j (conj out i)))))",
+            "[3 2 1]",
+        );
+        test_eq(
+            "(loop ([a & r] [1 2 3] sum 0) (if a (recur r (+ sum a)) sum))",
+            "6",
+        );
+        test_eq(
+            "(map (fn* (f) (f)) (loop (i 0 fs []) (if (< i 3) (recur (inc i) (conj fs (fn* () i))) fs)))",
+            "(0 1 2)",
+        );
+        test_eq("(loop (i 0) (if (< i 100000) (recur (inc i)) i))", "100000");
+        test_eq(
+            "(loop (i 0 f (fn* () i)) (if (< i 3) (recur (inc i) f) (f)))",
+            "0",
+        );
+    }
+
+    #[test]
+    fn test_multi_arity() {
+        let greet = "(fn* ([] :none) ([a] [a]) ([a b] [a b]) ([a b & more] more))";
+        test_eq(&format!("({})", greet), ":none");
+        test_eq(&format!("({} 1)", greet), "[1]");
+        test_eq(&format!("({} 1 2)", greet), "[1 2]");
+        test_eq(&format!("({} 1 2 3 4)", greet), "(3 4)");
+        test_eq("((fn* ([x & r] r) ([x] :one)) 1)", ":one");
+        test_eq("((fn* ([[a b]] (+ a b))) [1 2])", "3");
+        test_eq(
+            "(do (def! sum (fn* ([n] (sum n 0)) ([n acc] (if (zero? n) acc (sum (dec n) (+ acc n)))))) (sum 100))",
+            "5050",
+        );
+        let ast = Sexp::read_from(&mut Tokenizer::new(
+            "((fn* ([a] a) ([a b & c] c)))".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(
+            evaluate(ast, env_core())
+                .map(|sexp| sexp.to_string())
+                .unwrap_err(),
+            "fn* has no clause for 0 arguments, expected one of 1, 2 or more"
+        );
+        test_fail("(fn* ([a] a) ([b] b))");
+        test_fail("(fn* ([& a] a) ([b & c] b))");
+        test_fail("(fn* ([a] a a) ([b c] b))");
+    }
+
     fn test_fail(test: &str) {
         let ast = Sexp::read_from(&mut Tokenizer::new(test.to_string())).unwrap();
         assert!(evaluate(ast, env_core()).is_err());
     }
 
+    fn test_err(test: &str, expect: &str) {
+        let ast = Sexp::read_from(&mut Tokenizer::new(test.to_string())).unwrap();
+        let err = evaluate(ast, env_core()).map(|sexp| sexp.to_string());
+        assert_eq!(err.unwrap_err(), expect);
+    }
+
+    #[test]
+    fn test_error_messages() {
+        test_err("(double :a)", "double expected 1 number, received [:a]");
+        test_err("(inc nil)", "inc expected 1 number, received [nil]");
+        test_err("(+ 1 :a)", "+ expected numbers, received [1 :a]");
+        test_err("(count 1)", "count expected 1 List, received 1");
+        test_err("(<)", "< expected 1 or more arguments, received 0");
+        test_err(
+            "(rand-int 0)",
+            "rand-int expected 1 positive Integer, received 0",
+        );
+    }
+
     #[test]
     fn test_repl_env_expect_fail() {
-        test_fail("(+)");
-        test_fail("(+ 1)");
-        test_fail("(+ 1 2 3)");
+        test_fail("(-)");
+        test_fail("(/)");
+        test_fail("(+ nil)");
+        test_fail("(+ 1 2 nil)");
+        test_fail("(<)");
         test_fail("(+ + +)");
         test_fail("(+ + 1 2)");
+        test_fail("(/ 1 0)");
+        test_fail("(quot 1 0)");
+        test_fail("((fn* (a) a))");
+        test_fail("((fn* (a) a) 1 2)");
+        test_fail("(fn* (a 1) a)");
+        test_fail("(fn* (& a b) a)");
+        test_fail("(if)");
+        test_fail("(recur 1)");
+        test_fail("(loop (i 0) (+ 1 (recur i)))");
+        test_fail("(throw 1)");
+        test_fail("(let* (x 1) (do (if false (def! y 2)) y))");
+        test_fail("(pow 3 2000000000)");
+        test_fail("(pow 1/3 -2000000000)");
+        test_fail("(bit-shift-left 1 4000000000)");
+        test_fail("(bit-shift-left :a 1)");
+        test_fail("(try* (throw 1))");
+        test_fail("(try* 1 (catch* 2 3))");
+        test_fail("(map inc 1)");
+        test_fail("(apply + 1)");
+        test_fail("(rem 1 0)");
+        test_fail("(mod 1 0)");
+        test_fail("(/ 9223372036854775808 0)");
+        test_fail("(int ##NaN)");
+        test_fail("(int ##Inf)");
+        test_fail("(sqrt nil)");
+        test_fail("(/ 1/2 0)");
+        test_fail("(numerator 0.5)");
+        test_fail("(abs nil)");
+        test_fail("(min)");
+        test_fail("(max 1 nil)");
+        test_fail("(pow 0 -1)");
+        test_fail("(pow 2 9223372036854775807)");
+        test_fail("(bit-and 1)");
+        test_fail("(bit-or 1 1.0)");
+        test_fail("(bit-shift-left 1 -1)");
+        test_fail("(even? 1.0)");
+        test_fail("(zero? nil)");
+        test_fail("(rand-int 0)");
+        test_fail("(rand-nth [])");
+        test_fail("(shuffle 1)");
+        test_fail("(rand-seed! 1.5)");
+        test_fail("(nth [1] 1)");
+        test_fail("(nth [1] -1)");
+        test_fail("(assoc [1] 5 1)");
+        test_fail("(assoc {} :a)");
+        test_fail("(conj {} [:a])");
+        test_fail("(hash-map :a)");
+        test_fail("(rest 1)");
     }
 }
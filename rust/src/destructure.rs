@@ -0,0 +1,324 @@
+use crate::lazy;
+use crate::symbol::{self, Symbol};
+use crate::types::{tokens_to_string, Arity, Map, Native, Sexp};
+use im_rc::Vector;
+
+/**
+ * Slot holding the value a nested pattern is taken apart from. The space
+ * keeps the reader from ever producing it, so it cannot shadow user names.
+ */
+fn hidden(depth: usize) -> Symbol {
+    Symbol::new(&format!("destructure {}", depth))
+}
+
+fn list(items: impl IntoIterator<Item = Sexp>) -> Sexp {
+    Sexp::List(items.into_iter().collect())
+}
+
+fn quote(sexp: &Sexp) -> Sexp {
+    list([Sexp::Symbol(symbol::QUOTE), sexp.clone()])
+}
+
+fn native(
+    name: &str,
+    arity: Arity,
+    func: impl Fn(&[Sexp]) -> Result<Sexp, String> + 'static,
+) -> Sexp {
+    Sexp::Func(Native::new(name, arity, move |args, _| func(args)))
+}
+
+/**
+ * Flattens binding `pattern` to `val` into plain `let*` bindings, in order.
+ * Vec patterns take `[a b & rest :as all]` apart from a List, Vec, lazy seq
+ * or nil, with missing elements bound to nil. Map patterns take
+ * `{:keys [x y] :or {y 0} :as m}` or `{name :key}` apart from a Map or nil.
+ * Patterns nest, and `form` names the special form in errors.
+ */
+pub fn bindings(form: &str, pattern: &Sexp, val: Sexp) -> Result<Vec<(Symbol, Sexp)>, String> {
+    let mut out = Vec::new();
+    expand(form, pattern, val, 0, &mut out)?;
+    Ok(out)
+}
+
+fn expand(
+    form: &str,
+    pattern: &Sexp,
+    val: Sexp,
+    depth: usize,
+    out: &mut Vec<(Symbol, Sexp)>,
+) -> Result<(), String> {
+    match pattern {
+        Sexp::Symbol(sym) if *sym != symbol::AMPERSAND => out.push((*sym, val)),
+        Sexp::Vec(items) => expand_seq(form, pattern, items, val, depth, out)?,
+        Sexp::Map(entries) => expand_map(form, pattern, entries, val, depth, out)?,
+        _ => {
+            return Err(format!(
+                "{} expected a Symbol, Vec or Map pattern, got {}",
+                form, pattern
+            ))
+        }
+    }
+    Ok(())
+}
+
+/** Binds the value being taken apart to a hidden slot, after checking its shape */
+fn bind_checked(
+    form: &str,
+    pattern: &Sexp,
+    val: Sexp,
+    depth: usize,
+    out: &mut Vec<(Symbol, Sexp)>,
+) -> Sexp {
+    let (expected, matches): (_, fn(&Sexp) -> bool) = match pattern {
+        Sexp::Map(_) => ("a Map or nil", |sexp| {
+            matches!(sexp, Sexp::Map(_) | Sexp::Nil)
+        }),
+        _ => ("a List, Vec or nil", |sexp| {
+            matches!(
+                sexp,
+                Sexp::List(_) | Sexp::Vec(_) | Sexp::Lazy(_) | Sexp::Nil
+            )
+        }),
+    };
+    let message = format!("{} pattern {} expected {}", form, pattern, expected);
+    let check = native("destructure", Arity::exactly(1), move |args| {
+        match &args[0] {
+            sexp if matches(sexp) => Ok(sexp.clone()),
+            sexp => Err(format!(
+                "{}, received {} {}",
+                message,
+                sexp.type_name(),
+                sexp
+            )),
+        }
+    });
+    let slot = hidden(depth);
+    out.push((slot, list([check, val])));
+    Sexp::Symbol(slot)
+}
+
+fn expand_seq(
+    form: &str,
+    pattern: &Sexp,
+    items: &Vector<Sexp>,
+    val: Sexp,
+    depth: usize,
+    out: &mut Vec<(Symbol, Sexp)>,
+) -> Result<(), String> {
+    let seq = bind_checked(form, pattern, val, depth, out);
+    let invalid = || {
+        format!(
+            "{} pattern {} expected a pattern after & and :as",
+            form, pattern
+        )
+    };
+    let mut items = items.iter();
+    let mut index = 0;
+    while let Some(item) = items.next() {
+        match item {
+            Sexp::Symbol(sym) if *sym == symbol::AMPERSAND => {
+                let rest = items.next().ok_or_else(invalid)?;
+                let drop = native("nthnext", Arity::exactly(2), nthnext);
+                let val = list([drop, seq.clone(), Sexp::Integer(index)]);
+                expand(form, rest, val, depth + 1, out)?;
+            }
+            Sexp::Keyword(key) if key.as_str() == "as" => match items.next() {
+                Some(Sexp::Symbol(all)) => out.push((*all, seq.clone())),
+                _ => return Err(invalid()),
+            },
+            item => {
+                let nth = native("nth", Arity::exactly(2), nth);
+                let val = list([nth, seq.clone(), Sexp::Integer(index)]);
+                expand(form, item, val, depth + 1, out)?;
+                index += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn expand_map(
+    form: &str,
+    pattern: &Sexp,
+    entries: &Map,
+    val: Sexp,
+    depth: usize,
+    out: &mut Vec<(Symbol, Sexp)>,
+) -> Result<(), String> {
+    let map = bind_checked(form, pattern, val, depth, out);
+    let keyword = |name: &str| Sexp::Keyword(Symbol::new(name));
+    let defaults = match entries.get(&keyword("or")) {
+        Some(Sexp::Map(defaults)) => defaults.clone(),
+        Some(other) => {
+            return Err(format!(
+                "{} pattern {} expected a Map after :or, got {}",
+                form, pattern, other
+            ))
+        }
+        None => Default::default(),
+    };
+    let lookup = |name: Option<Symbol>, key: &Sexp| {
+        let get = native("get", Arity::exactly(2), get);
+        let found = list([get, map.clone(), quote(key)]);
+        match name.and_then(|name| defaults.get(&Sexp::Symbol(name))) {
+            Some(default) => {
+                let has = native("contains?", Arity::exactly(2), contains);
+                let has = list([has, map.clone(), quote(key)]);
+                list([Sexp::Symbol(symbol::IF), has, found, default.clone()])
+            }
+            None => found,
+        }
+    };
+
+    if let Some(all) = entries.get(&keyword("as")) {
+        let Sexp::Symbol(all) = all else {
+            return Err(format!(
+                "{} pattern {} expected a Symbol after :as, got {}",
+                form, pattern, all
+            ));
+        };
+        out.push((*all, map.clone()));
+    }
+    for (key, val) in entries {
+        match key {
+            Sexp::Keyword(option) if option.as_str() == "keys" => {
+                let (Sexp::Vec(names) | Sexp::List(names)) = val else {
+                    return Err(format!(
+                        "{} pattern {} expected a Vec of Symbols after :keys, got {}",
+                        form, pattern, val
+                    ));
+                };
+                for name in names {
+                    let Sexp::Symbol(name) = name else {
+                        return Err(format!(
+                            "{} pattern {} expected Symbols in :keys, got {}",
+                            form,
+                            pattern,
+                            tokens_to_string(names)
+                        ));
+                    };
+                    out.push((*name, lookup(Some(*name), &keyword(name.as_str()))));
+                }
+            }
+            Sexp::Keyword(option) if matches!(option.as_str(), "or" | "as") => {}
+            Sexp::Symbol(name) => out.push((*name, lookup(Some(*name), val))),
+            key => expand(form, key, lookup(None, val), depth + 1, out)?,
+        }
+    }
+    Ok(())
+}
+
+fn nth(args: &[Sexp]) -> Result<Sexp, String> {
+    let [seq, Sexp::Integer(index)] = args else {
+        unreachable!("destructure passes an index");
+    };
+    let index = *index as usize;
+    match seq {
+        Sexp::List(items) | Sexp::Vec(items) => Ok(items.get(index).cloned().unwrap_or(Sexp::Nil)),
+        seq => lazy::iter(seq).nth(index).unwrap_or(Ok(Sexp::Nil)),
+    }
+}
+
+/** Elements from `index` on, or nil if there are none */
+fn nthnext(args: &[Sexp]) -> Result<Sexp, String> {
+    let [seq, Sexp::Integer(index)] = args else {
+        unreachable!("destructure passes an index");
+    };
+    let mut rest = seq.clone();
+    for _ in 0..*index {
+        match lazy::uncons(&rest)? {
+            Some((_, tail)) => rest = tail,
+            None => return Ok(Sexp::Nil),
+        }
+    }
+    Ok(match lazy::uncons(&rest)? {
+        Some(_) => rest,
+        None => Sexp::Nil,
+    })
+}
+
+fn get(args: &[Sexp]) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Map(map), key] => Ok(map.get(key).cloned().unwrap_or(Sexp::Nil)),
+        _ => Ok(Sexp::Nil),
+    }
+}
+
+fn contains(args: &[Sexp]) -> Result<Sexp, String> {
+    match args {
+        [Sexp::Map(map), key] => Ok(Sexp::Bool(map.contains_key(key))),
+        _ => Ok(Sexp::Bool(false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_destructuring() {
+        let interp = Interpreter::new();
+        let eval = |src: &str| interp.eval_str(src).map(|sexp| sexp.to_string());
+        assert_eq!(
+            eval("(let* ([a b & more :as all] (list 1 2 3 4)) [a b more all])").unwrap(),
+            "[1 2 (3 4) (1 2 3 4)]"
+        );
+        assert_eq!(
+            eval("(let* ([a [b c]] [1 [2]]) [a b c])").unwrap(),
+            "[1 2 nil]"
+        );
+        assert_eq!(
+            eval("(let* ([x & xs] (range)) [x (first xs)])").unwrap(),
+            "[0 1]"
+        );
+        assert_eq!(eval("(let* ([a & r] [1]) r)").unwrap(), "nil");
+        assert_eq!(
+            eval("(let* ({:keys [x y] :or {y 0} :as m} {:x 1}) [x y m])").unwrap(),
+            "[1 0 {:x 1}]"
+        );
+        assert_eq!(
+            eval("(let* ({[a b] :pos n \"name\"} {:pos [3 4] \"name\" :p}) [a b n])").unwrap(),
+            "[3 4 :p]"
+        );
+        assert_eq!(
+            eval("(let* ({:keys [y] :or {y 2}} {:y nil}) y)").unwrap(),
+            "nil"
+        );
+        eval("(def! dist (fn* ([x1 y1] {:keys [x y]}) (+ (- x x1) (- y y1))))").unwrap();
+        assert_eq!(eval("(dist [1 2] {:x 4 :y 6})").unwrap(), "7");
+        assert_eq!(
+            eval("((fn* (a & [b c]) [a b c]) 1 2 3)").unwrap(),
+            "[1 2 3]"
+        );
+        assert_eq!(
+            eval("(let* (f (fn* ([a b]) (+ a b))) (map f [[1 2] [3 4]]))").unwrap(),
+            "(3 7)"
+        );
+    }
+
+    #[test]
+    fn test_destructuring_errors() {
+        let interp = Interpreter::new();
+        let eval = |src: &str| interp.eval_str(src).map(|sexp| sexp.to_string());
+        assert_eq!(
+            eval("(let* ([a b] 5) a)").unwrap_err(),
+            "let* pattern [a b] expected a List, Vec or nil, received Integer 5"
+        );
+        assert_eq!(
+            eval("(let* ([a {:keys [k]}] [1 [2]]) k)").unwrap_err(),
+            "let* pattern {:keys [k]} expected a Map or nil, received Vec [2]"
+        );
+        assert_eq!(
+            eval("((fn* ([a]) a) :x)").unwrap_err(),
+            "fn* pattern [a] expected a List, Vec or nil, received Keyword :x"
+        );
+        assert_eq!(
+            eval("(let* ([a 1] [1 2]) a)").unwrap_err(),
+            "let* expected a Symbol, Vec or Map pattern, got 1"
+        );
+        assert_eq!(
+            eval("(let* ([a &] [1 2]) a)").unwrap_err(),
+            "let* pattern [a &] expected a pattern after & and :as"
+        );
+    }
+}
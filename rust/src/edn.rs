@@ -0,0 +1,666 @@
+use crate::lazy;
+use crate::number::{self, Float};
+use crate::symbol::Symbol;
+use crate::types::{Map, Set, Sexp};
+use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+lazy_static! {
+    static ref INTEGER_RE: Regex = Regex::new(r"^[-+]?\d+N?$").unwrap();
+    static ref RATIO_RE: Regex = Regex::new(r"^[-+]?\d+/\d+$").unwrap();
+    static ref FLOAT_RE: Regex = Regex::new(r"^[-+]?\d+(\.\d*)?([eE][-+]?\d+)?M?$").unwrap();
+    static ref INST_RE: Regex = Regex::new(
+        r"^\d{4}(-\d{2}(-\d{2}(T\d{2}(:\d{2}(:\d{2}(\.\d+)?)?)?(Z|[-+]\d{2}:\d{2})?)?)?)?$"
+    )
+    .unwrap();
+    static ref UUID_RE: Regex =
+        Regex::new(r"^[[:xdigit:]]{8}-([[:xdigit:]]{4}-){3}[[:xdigit:]]{12}$").unwrap();
+}
+
+/** Deepest nesting of collections, tags and discards read, as in `json` */
+pub const MAX_DEPTH: usize = 128;
+
+/** Turns the element following `#tag` into a value, or rejects it */
+pub type TagFn = dyn Fn(Sexp) -> Result<Sexp, String>;
+
+/** Handles elements whose tag has no `TagFn` registered */
+pub type FallbackFn = dyn Fn(Symbol, Sexp) -> Result<Sexp, String>;
+
+/**
+ * Reads EDN, the data subset of Clojure syntax. Nothing read is evaluated:
+ * `#tag` elements are passed to the handler registered for the tag, and
+ * unknown tags are an error unless a fallback is set.
+ */
+#[derive(Clone)]
+pub struct Edn {
+    handlers: HashMap<Symbol, Rc<TagFn>>,
+    fallback: Option<Rc<FallbackFn>>,
+}
+
+impl Default for Edn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/** Checks the element is a string matching `re`, keeping it tagged */
+fn validated(tag: &'static str, re: &'static Regex) -> impl Fn(Sexp) -> Result<Sexp, String> {
+    move |sexp| match &sexp {
+        Sexp::String(s) if re.is_match(s) => Ok(Sexp::Tagged(Symbol::new(tag), Box::new(sexp))),
+        _ => Err(format!(
+            "EDN #{} expected a valid String, received {}",
+            tag, sexp
+        )),
+    }
+}
+
+impl Edn {
+    /** Reader with the built in `#inst` and `#uuid` tags, kept as `Sexp::Tagged` */
+    pub fn new() -> Self {
+        let mut edn = Self {
+            handlers: HashMap::new(),
+            fallback: None,
+        };
+        edn.tag("inst", validated("inst", &INST_RE));
+        edn.tag("uuid", validated("uuid", &UUID_RE));
+        edn
+    }
+
+    /** Registers or replaces the handler for `#name` */
+    pub fn tag(&mut self, name: &str, handler: impl Fn(Sexp) -> Result<Sexp, String> + 'static) {
+        self.handlers.insert(Symbol::new(name), Rc::new(handler));
+    }
+
+    /**
+     * Handles every unregistered tag, for example keeping them all with
+     * `|tag, sexp| Ok(Sexp::Tagged(tag, Box::new(sexp)))`.
+     */
+    pub fn fallback(&mut self, handler: impl Fn(Symbol, Sexp) -> Result<Sexp, String> + 'static) {
+        self.fallback = Some(Rc::new(handler));
+    }
+
+    /** Reads exactly one form from `src` */
+    pub fn read_str(&self, src: &str) -> Result<Sexp, String> {
+        let mut reader = Reader {
+            src,
+            pos: 0,
+            edn: self,
+            depth: 0,
+        };
+        reader.skip()?;
+        let sexp = reader.form()?;
+        reader.skip()?;
+        match reader.peek() {
+            None => Ok(sexp),
+            Some(_) => Err(reader.unexpected("end of input")),
+        }
+    }
+
+    /** Reads every top level form in `src` */
+    pub fn read_all(&self, src: &str) -> Result<Vec<Sexp>, String> {
+        let mut reader = Reader {
+            src,
+            pos: 0,
+            edn: self,
+            depth: 0,
+        };
+        let mut forms = Vec::new();
+        loop {
+            reader.skip()?;
+            if reader.peek().is_none() {
+                return Ok(forms);
+            }
+            forms.push(reader.form()?);
+        }
+    }
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    pos: usize,
+    edn: &'a Edn,
+    depth: usize,
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ',' | '(' | ')' | '[' | ']' | '{' | '}' | '"' | ';')
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!(
+                "EDN expected {}, received '{}' at byte {}",
+                expected, c, self.pos
+            ),
+            None => format!("EDN expected {}, received EOF", expected),
+        }
+    }
+
+    /** Skips whitespace, commas, comments and forms discarded with `#_` */
+    fn skip(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() || c == ',' => self.pos += c.len_utf8(),
+                Some(';') => match self.src[self.pos..].find('\n') {
+                    Some(end) => self.pos += end,
+                    None => self.pos = self.src.len(),
+                },
+                Some('#') if self.src[self.pos..].starts_with("#_") => {
+                    self.nested(|reader| {
+                        reader.pos += 2;
+                        reader.skip()?;
+                        reader.form()
+                    })?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /** Characters up to the next delimiter */
+    fn token(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(c) = self.peek().filter(|c| !is_delimiter(*c)) {
+            self.pos += c.len_utf8();
+        }
+        &self.src[start..self.pos]
+    }
+
+    /** Reads a form containing others, failing past `MAX_DEPTH` rather than overflowing the stack */
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!(
+                "EDN nests deeper than {} at byte {}",
+                MAX_DEPTH, self.pos
+            ));
+        }
+        self.depth += 1;
+        let read = read(self);
+        self.depth -= 1;
+        read
+    }
+
+    fn form(&mut self) -> Result<Sexp, String> {
+        match self.peek() {
+            Some('(') => self.nested(|reader| {
+                reader.pos += 1;
+                reader.seq(')').map(|seq| Sexp::List(seq.into()))
+            }),
+            Some('[') => self.nested(|reader| {
+                reader.pos += 1;
+                reader.seq(']').map(|seq| Sexp::Vec(seq.into()))
+            }),
+            Some('{') => self.nested(|reader| {
+                reader.pos += 1;
+                reader.map()
+            }),
+            Some('"') => self.string(),
+            Some('\\') => self.char(),
+            Some('#') => self.nested(Self::dispatch),
+            Some(')' | ']' | '}') | None => Err(self.unexpected("form")),
+            Some(_) => {
+                let start = self.pos;
+                let token = self.token();
+                atom(token).map_err(|err| format!("{} at byte {}", err, start))
+            }
+        }
+    }
+
+    fn seq(&mut self, closer: char) -> Result<Vec<Sexp>, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip()?;
+            match self.peek() {
+                Some(c) if c == closer => {
+                    self.pos += 1;
+                    return Ok(items);
+                }
+                None => return Err(self.unexpected(&format!("'{}'", closer))),
+                Some(_) => items.push(self.form()?),
+            }
+        }
+    }
+
+    fn map(&mut self) -> Result<Sexp, String> {
+        let start = self.pos - 1;
+        let forms = self.seq('}')?;
+        if forms.len() % 2 == 1 {
+            return Err(format!(
+                "EDN map expected an even number of forms at byte {}",
+                start
+            ));
+        }
+        let mut forms = forms.into_iter();
+        let mut map = Map::default();
+        while let (Some(key), Some(val)) = (forms.next(), forms.next()) {
+            if map.contains_key(&key) {
+                return Err(format!(
+                    "EDN map has duplicate key {} at byte {}",
+                    key, start
+                ));
+            }
+            map.insert(key, val);
+        }
+        Ok(Sexp::Map(map))
+    }
+
+    fn dispatch(&mut self) -> Result<Sexp, String> {
+        let start = self.pos;
+        self.pos += 1;
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut set = Set::default();
+                for item in self.seq('}')? {
+                    if set.contains(&item) {
+                        return Err(format!(
+                            "EDN set has duplicate element {} at byte {}",
+                            item, start
+                        ));
+                    }
+                    set.insert(item);
+                }
+                Ok(Sexp::Set(set))
+            }
+            Some('#') => match self.token() {
+                "#Inf" => Ok(Sexp::Float(Float(f64::INFINITY))),
+                "#-Inf" => Ok(Sexp::Float(Float(f64::NEG_INFINITY))),
+                "#NaN" => Ok(Sexp::Float(Float(f64::NAN))),
+                token => Err(format!(
+                    "EDN symbolic value '#{}' is invalid at byte {}",
+                    token, start
+                )),
+            },
+            Some(':') => {
+                self.pos += 1;
+                let ns = self.token().to_string();
+                self.skip()?;
+                if ns.is_empty() || self.peek() != Some('{') {
+                    return Err(self.unexpected("namespaced map"));
+                }
+                self.pos += 1;
+                let Sexp::Map(map) = self.map()? else {
+                    unreachable!("map reads a Sexp::Map")
+                };
+                map.into_iter()
+                    .map(|(key, val)| Ok((qualify(&ns, key)?, val)))
+                    .collect::<Result<_, String>>()
+                    .map(Sexp::Map)
+                    .map_err(|err| format!("EDN {} at byte {}", err, start))
+            }
+            Some(c) if c.is_alphabetic() => {
+                let tag = Symbol::from_data(self.token())
+                    .map_err(|err| format!("EDN {} at byte {}", err, start))?;
+                self.skip()?;
+                let sexp = self.form()?;
+                match (self.edn.handlers.get(&tag), &self.edn.fallback) {
+                    (Some(handler), _) => handler(sexp),
+                    (None, Some(fallback)) => fallback(tag, sexp),
+                    (None, None) => Err(format!("EDN has no reader for tag #{}", tag)),
+                }
+                .map_err(|err| format!("{} at byte {}", err, start))
+            }
+            _ => Err(self.unexpected("dispatch character")),
+        }
+    }
+
+    fn string(&mut self) -> Result<Sexp, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(Sexp::String(s)),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('n') => '\n',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.unicode()?,
+                        Some(c @ ('\\' | '"')) => c,
+                        _ => {
+                            return Err(format!(
+                                "EDN string has an invalid escape before byte {}",
+                                self.pos
+                            ))
+                        }
+                    };
+                    s.push(c);
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.unexpected("'\"'")),
+            }
+        }
+    }
+
+    fn unicode(&mut self) -> Result<char, String> {
+        let digits = self.src.get(self.pos..self.pos + 4);
+        match digits.and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(code) => {
+                self.pos += 4;
+                Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+            }
+            None => Err(self.unexpected("4 hex digits")),
+        }
+    }
+
+    fn char(&mut self) -> Result<Sexp, String> {
+        let start = self.pos;
+        self.pos += 1;
+        let Some(first) = self.next() else {
+            return Err(self.unexpected("character"));
+        };
+        let rest = self.token();
+        let c = match (first, rest) {
+            (c, "") => Some(c),
+            ('n', "ewline") => Some('\n'),
+            ('r', "eturn") => Some('\r'),
+            ('s', "pace") => Some(' '),
+            ('t', "ab") => Some('\t'),
+            ('u', hex) if hex.len() == 4 => {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            }
+            _ => None,
+        };
+        c.map(Sexp::Char).ok_or_else(|| {
+            format!(
+                "EDN character '{}' is invalid at byte {}",
+                &self.src[start..self.pos],
+                start
+            )
+        })
+    }
+}
+
+/** Adds the namespace of a `#:ns{}` map to keys without one, `:_/key` opting out */
+fn qualify(ns: &str, key: Sexp) -> Result<Sexp, String> {
+    let sexp = match key {
+        Sexp::Keyword(sym) => match sym.as_str().split_once('/') {
+            Some(("_", name)) => Sexp::Keyword(Symbol::from_data(name)?),
+            Some(_) => key,
+            None => Sexp::Keyword(Symbol::from_data(&format!("{}/{}", ns, sym))?),
+        },
+        key => key,
+    };
+    Ok(sexp)
+}
+
+/** A symbol or keyword name, optionally with one namespace prefix */
+fn valid_name(name: &str) -> bool {
+    match name.split_once('/') {
+        _ if name == "/" => true,
+        Some((ns, name)) => !ns.is_empty() && !name.is_empty() && !name.contains('/'),
+        None => !name.is_empty(),
+    }
+}
+
+fn atom(token: &str) -> Result<Sexp, String> {
+    let invalid = || format!("EDN token '{}' is invalid", token);
+    let name = |name| Symbol::from_data(name).map_err(|err| format!("EDN {}", err));
+    let sexp = match token {
+        "nil" => Sexp::Nil,
+        "true" => Sexp::Bool(true),
+        "false" => Sexp::Bool(false),
+        int if INTEGER_RE.is_match(int) => int
+            .trim_end_matches('N')
+            .trim_start_matches('+')
+            .parse::<BigInt>()
+            .map(number::normalize)
+            .map_err(|_| invalid())?,
+        ratio if RATIO_RE.is_match(ratio) => {
+            let (numer, denom) = ratio.trim_start_matches('+').split_once('/').unwrap();
+            let (numer, denom) = (
+                numer.parse::<BigInt>().map_err(|_| invalid())?,
+                denom.parse::<BigInt>().map_err(|_| invalid())?,
+            );
+            if denom.is_zero() {
+                return Err(invalid());
+            }
+            number::normalize_ratio(BigRational::new(numer, denom))
+        }
+        float if FLOAT_RE.is_match(float) => float
+            .trim_end_matches('M')
+            .parse::<f64>()
+            .map(|num| Sexp::Float(Float(num)))
+            .map_err(|_| invalid())?,
+        keyword if keyword.starts_with(':') => match valid_name(&keyword[1..]) {
+            true if !keyword.starts_with("::") => Sexp::Keyword(name(&keyword[1..])?),
+            _ => return Err(invalid()),
+        },
+        symbol if valid_name(symbol) && !symbol.starts_with(|c: char| c.is_ascii_digit()) => {
+            Sexp::Symbol(name(symbol)?)
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(sexp)
+}
+
+/** Printed form of a character, as `\a` or `\newline` */
+pub fn char_name(c: char) -> String {
+    match c {
+        '\n' => "\\newline".to_string(),
+        '\r' => "\\return".to_string(),
+        ' ' => "\\space".to_string(),
+        '\t' => "\\tab".to_string(),
+        c if c.is_control() => format!("\\u{:04x}", c as u32),
+        c => format!("\\{}", c),
+    }
+}
+
+/** Prints `sexp` as EDN, failing on functions, which have no EDN form */
+pub fn write_str(sexp: &Sexp) -> Result<String, String> {
+    let mut out = String::new();
+    write(sexp, &mut out)?;
+    Ok(out)
+}
+
+fn write_all<'a>(
+    open: &str,
+    items: impl Iterator<Item = &'a Sexp>,
+    close: char,
+    out: &mut String,
+) -> Result<(), String> {
+    out.push_str(open);
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write(item, out)?;
+    }
+    out.push(close);
+    Ok(())
+}
+
+fn write(sexp: &Sexp, out: &mut String) -> Result<(), String> {
+    match sexp {
+        Sexp::String(s) => {
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        Sexp::List(items) => write_all("(", items.iter(), ')', out)?,
+        Sexp::Vec(items) => write_all("[", items.iter(), ']', out)?,
+        Sexp::Set(set) => write_all("#{", set.iter(), '}', out)?,
+        Sexp::Lazy(_) => {
+            let items = lazy::iter(sexp).collect::<Result<Vec<Sexp>, _>>()?;
+            write_all("(", items.iter(), ')', out)?
+        }
+        Sexp::Map(map) => write_all("{", map.iter().flat_map(|(k, v)| [k, v]), '}', out)?,
+        Sexp::Tagged(tag, sexp) => {
+            write!(out, "#{} ", tag).unwrap();
+            write(sexp, out)?;
+        }
+        Sexp::Func(_) | Sexp::Closure(_) => {
+            return Err(format!(
+                "EDN cannot represent {} {}",
+                sexp.type_name(),
+                sexp
+            ))
+        }
+        Sexp::Integer(_)
+        | Sexp::BigInt(_)
+        | Sexp::Ratio(_)
+        | Sexp::Float(_)
+        | Sexp::Bool(_)
+        | Sexp::Symbol(_)
+        | Sexp::Keyword(_)
+        | Sexp::Char(_)
+        | Sexp::Nil => write!(out, "{}", sexp).unwrap(),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(src: &str) -> String {
+        Edn::new().read_str(src).unwrap().to_string()
+    }
+
+    fn read_err(src: &str) -> String {
+        Edn::new()
+            .read_str(src)
+            .map(|sexp| sexp.to_string())
+            .unwrap_err()
+    }
+
+    #[test]
+    fn test_read_str() {
+        assert_eq!(read("(a, [1 -2.5 +3] {:k nil})"), "(a [1 -2.5 3] {:k nil})");
+        assert_eq!(read("#{1}"), "#{1}");
+        assert_eq!(read(r"[\a \newline \A \space]"), r"[\a \newline \A \space]");
+        assert_eq!(read("[1 #_ 2 #_ #_ 3 4 5] ; done"), "[1 5]");
+        assert_eq!(
+            read("[:ns/a ns/b / 12N 1.5M 3/6]"),
+            "[:ns/a ns/b / 12 1.5 1/2]"
+        );
+        assert_eq!(read("#:user{:id 1}"), "{:user/id 1}");
+        assert!(
+            Edn::new().read_str("#:user{:_/id 1 :x/id 2}").unwrap()
+                == Edn::new().read_str("{:id 1 :x/id 2}").unwrap()
+        );
+        assert!(Edn::new().read_str(r#""a\tb""#).unwrap() == Sexp::String("a\tb".to_string()));
+        assert_eq!(
+            read(r#"#inst "1985-04-12T23:20:50.52Z""#),
+            r#"#inst "1985-04-12T23:20:50.52Z""#
+        );
+        let uuid = r#"#uuid "f81d4fae-7dec-11d0-a765-00a0c91e6bf6""#;
+        assert_eq!(read(uuid), uuid);
+        assert!(Edn::new().read_all("1 ; x\n #_ 2").unwrap().len() == 1);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert_eq!(
+            read_err("#{1 1}"),
+            "EDN set has duplicate element 1 at byte 0"
+        );
+        assert_eq!(
+            read_err("{:a 1 :a 2}"),
+            "EDN map has duplicate key :a at byte 0"
+        );
+        assert_eq!(
+            read_err("#point [1 2]"),
+            "EDN has no reader for tag #point at byte 0"
+        );
+        assert_eq!(
+            read_err(r#"#inst "yesterday""#),
+            r#"EDN #inst expected a valid String, received "yesterday" at byte 0"#
+        );
+        assert_eq!(read_err("[1 ::a]"), "EDN token '::a' is invalid at byte 3");
+        assert_eq!(
+            read_err(r"\spade"),
+            r"EDN character '\spade' is invalid at byte 0"
+        );
+        assert_eq!(read_err("(1"), "EDN expected ')', received EOF");
+        assert!(Edn::new().read_str("1 2").is_err());
+        assert!(Edn::new().read_str("#_ 1").is_err());
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(Edn::new().read_str(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            read_err(&nested(20_000)),
+            "EDN nests deeper than 128 at byte 128"
+        );
+        assert!(Edn::new().read_str(&"#_ ".repeat(20_000)).is_err());
+        assert!(Edn::new().read_str(&"#{#inst ".repeat(200)).is_err());
+    }
+
+    #[test]
+    fn test_data_names_are_bounded() {
+        let names: Vec<String> = (0..1100).map(|n| format!(":{:0>1000}", n)).collect();
+        let keywords = format!("[{}]", names.join(" "));
+        assert!(Edn::new()
+            .read_str(&keywords)
+            .is_err_and(|err| err.starts_with("EDN cannot intern")));
+        assert!(Edn::new().read_str(&keywords.replace(':', "x")).is_err());
+        assert_eq!(read("[:a b]"), "[:a b]");
+    }
+
+    #[test]
+    fn test_tag_handlers() {
+        let mut edn = Edn::new();
+        edn.tag("point", |sexp| match sexp {
+            Sexp::Vec(xy) if xy.len() == 2 => Ok(Sexp::Map(
+                Map::default()
+                    .update(Sexp::Keyword(Symbol::new("x")), xy[0].clone())
+                    .update(Sexp::Keyword(Symbol::new("y")), xy[1].clone()),
+            )),
+            sexp => Err(format!("#point expected [x y], received {}", sexp)),
+        });
+        let point = edn.read_str("#point [1 2]").unwrap();
+        assert!(point == Edn::new().read_str("{:x 1 :y 2}").unwrap());
+        assert!(edn.read_str("#point 1").is_err());
+        assert!(edn.read_str("#other 1").is_err());
+        edn.fallback(|tag, sexp| Ok(Sexp::Tagged(tag, Box::new(sexp))));
+        assert_eq!(
+            edn.read_str("#other (+ 1 2)").unwrap().to_string(),
+            "#other (+ 1 2)"
+        );
+    }
+
+    #[test]
+    fn test_write_str() {
+        let src = r#"(a [1 2.5 \c "q\"\n"] #{nil} #inst "2024-01-01")"#;
+        let sexp = Edn::new().read_str(src).unwrap();
+        let written = write_str(&sexp).unwrap();
+        assert_eq!(written, src);
+        assert!(Edn::new().read_str(&written).unwrap() == sexp);
+        let plus = crate::core::env_core().borrow().get("+").unwrap();
+        assert_eq!(
+            write_str(&Sexp::Vec(vec![plus].into())).unwrap_err(),
+            "EDN cannot represent Func <func>"
+        );
+    }
+}
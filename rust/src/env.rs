@@ -1,5 +1,11 @@
-use crate::types::{tokens_to_string, Sexp};
-use std::cell::RefCell;
+use crate::gc;
+use crate::namespace::Namespaces;
+use crate::resolve::{resolve_in, top_level_do, Address, Clause, Expr, Global};
+use crate::rng::Rng;
+use crate::symbol::{self, Symbol};
+use crate::types::{Closure, Sexp};
+use im_rc::Vector;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -7,103 +13,416 @@ pub type Env = Rc<RefCell<EnvStruct>>;
 
 pub struct EnvStruct {
     outer: Option<Env>,
-    data: HashMap<String, Sexp>,
+    data: HashMap<Symbol, Sexp>,
+    rng: Option<Rng>,
+    /** Message and value of the last `throw`, kept for the `catch*` that receives it */
+    thrown: Option<(String, Sexp)>,
+    namespaces: Namespaces,
 }
 
 pub fn env_new(outer: Option<Env>) -> Env {
     Rc::new(RefCell::new(EnvStruct {
         outer,
         data: HashMap::new(),
+        rng: None,
+        thrown: None,
+        namespaces: Namespaces::default(),
     }))
 }
 
+fn with_root<T>(env: &Env, f: impl FnOnce(&mut EnvStruct) -> T) -> T {
+    let outer = env.borrow().outer.clone();
+    match outer {
+        Some(outer) => with_root(&outer, f),
+        None => f(&mut env.borrow_mut()),
+    }
+}
+
+/** Runs `f` on the PRNG of the root env, seeding it from the clock on first use */
+pub fn with_rng<T>(env: &Env, f: impl FnOnce(&mut Rng) -> T) -> T {
+    with_root(env, |root| f(root.rng.get_or_insert_with(Rng::from_time)))
+}
+
+/**
+ * Error message `throw` raises for `value`, remembering the value itself so
+ * `catch*` can bind it rather than the message
+ */
+pub fn raise(env: &Env, value: Sexp) -> String {
+    let message = match &value {
+        Sexp::String(message) => message.clone(),
+        value => value.to_string(),
+    };
+    with_root(env, |root| root.thrown = Some((message.clone(), value)));
+    message
+}
+
+/** Value `catch*` binds for `error`, the thrown value if it came from `throw` */
+pub fn caught(env: &Env, error: String) -> Sexp {
+    with_root(env, |root| match &root.thrown {
+        Some((message, value)) if *message == error => value.clone(),
+        _ => Sexp::String(error),
+    })
+}
+
 impl EnvStruct {
-    pub fn set(&mut self, symbol: &str, sexp: Sexp) {
-        self.data.insert(symbol.to_string(), sexp);
+    pub fn set(&mut self, symbol: impl Into<Symbol>, sexp: Sexp) {
+        self.data.insert(symbol.into(), sexp);
     }
 
-    pub fn get(&self, symbol: &str) -> Option<Sexp> {
+    pub fn get(&self, symbol: impl Into<Symbol>) -> Option<Sexp> {
+        let symbol = symbol.into();
         self.data
-            .get(symbol)
+            .get(&symbol)
             .cloned()
-            .or(self.outer.as_ref().and_then(|env| env.borrow().get(symbol)))
+            .or_else(|| self.outer.as_ref().and_then(|env| env.borrow().get(symbol)))
+    }
+
+    /** Whether `symbol` is bound here rather than in an outer env */
+    pub fn defines(&self, symbol: Symbol) -> bool {
+        self.data.contains_key(&symbol)
+    }
+
+    pub fn outer(&self) -> Option<Env> {
+        self.outer.clone()
+    }
+
+    /** Value of a global, where `self` holds `user` and the other namespaces */
+    pub fn global(&self, global: Global) -> Option<Sexp> {
+        match global.ns {
+            symbol::USER => self.get(global.name),
+            ns => self
+                .namespaces
+                .env(ns)
+                .and_then(|ns| ns.borrow().get(global.name)),
+        }
+    }
+
+    pub fn namespaces(&self) -> &Namespaces {
+        &self.namespaces
+    }
+
+    pub fn namespaces_mut(&mut self) -> &mut Namespaces {
+        &mut self.namespaces
+    }
+}
+
+/** Slots of a `let*` or function call frame, addressed by `resolve::Address` */
+pub type Frame = Rc<FrameStruct>;
+
+pub struct FrameStruct {
+    outer: Option<Frame>,
+    slots: RefCell<Vec<Sexp>>,
+}
+
+thread_local! {
+    static FRAMES_ALIVE: Cell<usize> = const { Cell::new(0) };
+}
+
+/** Number of frames allocated on this thread and not yet dropped */
+pub fn frames_alive() -> usize {
+    FRAMES_ALIVE.get()
+}
+
+pub fn frame_new(size: usize, outer: Option<Frame>) -> Frame {
+    FRAMES_ALIVE.set(FRAMES_ALIVE.get() + 1);
+    Rc::new(FrameStruct {
+        outer,
+        slots: RefCell::new(vec![Sexp::Nil; size]),
+    })
+}
+
+impl FrameStruct {
+    pub fn get(&self, address: Address) -> Sexp {
+        let mut frame = self;
+        for _ in 0..address.depth {
+            frame = frame
+                .outer
+                .as_deref()
+                .expect("address resolved past frames");
+        }
+        frame.slots.borrow()[address.slot].clone()
+    }
+
+    pub fn set(&self, slot: usize, sexp: Sexp) {
+        self.slots.borrow_mut()[slot] = sexp;
+    }
+
+    pub fn outer(&self) -> Option<Frame> {
+        self.outer.clone()
+    }
+
+    /**
+     * Copies any part of a List, Vec or Map in the slots that holds a closure
+     * and is shared with other values, so `for_each_edge` can count the
+     * closures in them as owned by this frame
+     */
+    pub fn unshare(&self) {
+        for sexp in self.slots.borrow_mut().iter_mut() {
+            unshare(sexp);
+        }
+    }
+
+    /**
+     * Visits the frames this one references, through `outer` and closures in
+     * its slots. `owned` is false for closures only reachable through a Set,
+     * a map key or a lazy seq, which `unshare` cannot copy.
+     */
+    pub fn for_each_edge(&self, mut f: impl FnMut(&Frame, bool)) {
+        if let Some(outer) = &self.outer {
+            f(outer, true);
+        }
+        for sexp in self.slots.borrow().iter() {
+            visit_frames(sexp, true, &mut f);
+        }
+    }
+
+    /** Drops every slot, breaking any cycle through this frame */
+    pub fn clear(&self) {
+        let size = self.slots.borrow().len();
+        let slots = self.slots.replace(vec![Sexp::Nil; size]);
+        drop(slots);
+    }
+}
+
+impl Drop for FrameStruct {
+    fn drop(&mut self) {
+        FRAMES_ALIVE.set(FRAMES_ALIVE.get() - 1);
+    }
+}
+
+fn visit_frames(sexp: &Sexp, owned: bool, f: &mut impl FnMut(&Frame, bool)) {
+    match sexp {
+        Sexp::Closure(Closure {
+            frame: Some(frame), ..
+        }) => f(frame, owned),
+        Sexp::List(list) | Sexp::Vec(list) => list.iter().for_each(|s| visit_frames(s, owned, f)),
+        Sexp::Map(map) => map.iter().for_each(|(key, val)| {
+            visit_frames(key, false, f);
+            visit_frames(val, owned, f);
+        }),
+        Sexp::Set(set) => set.iter().for_each(|s| visit_frames(s, false, f)),
+        Sexp::Tagged(_, sexp) => visit_frames(sexp, owned, f),
+        _ => {}
     }
 }
 
-fn first_equal(list: &[Sexp], str: &str) -> bool {
-    matches!(&list[0], Sexp::Symbol(sym) if sym == str)
+fn holds_closure(sexp: &Sexp) -> bool {
+    match sexp {
+        Sexp::Closure(Closure { frame: Some(_), .. }) => true,
+        Sexp::List(list) | Sexp::Vec(list) => list.iter().any(holds_closure),
+        Sexp::Map(map) => map.values().any(holds_closure),
+        Sexp::Tagged(_, sexp) => holds_closure(sexp),
+        _ => false,
+    }
 }
 
+/** Mutable access copies shared nodes, leaving ones only `sexp` holds alone */
+fn unshare(sexp: &mut Sexp) {
+    if !holds_closure(sexp) {
+        return;
+    }
+    match sexp {
+        Sexp::List(list) | Sexp::Vec(list) => list.iter_mut().for_each(unshare),
+        Sexp::Map(map) => map.iter_mut().for_each(|(_, val)| unshare(val)),
+        Sexp::Tagged(_, sexp) => unshare(sexp),
+        _ => {}
+    }
+}
+
+/** Resolves `ast` in the current namespace of `env`, then evaluates it */
 pub fn evaluate(ast: Sexp, env: Env) -> Result<Sexp, String> {
-    match ast {
-        Sexp::List(list) if list.is_empty() => Ok(Sexp::List(list)),
-        Sexp::Vec(list) if list.is_empty() => Ok(Sexp::Vec(list)),
-        Sexp::List(list) if first_equal(&list, "def!") => {
-            let [_, Sexp::Symbol(key), val] = list.as_slice() else {
-                return Err(format!(
-                    "def! expected [Key, Val], got {}",
-                    tokens_to_string(&list)
-                ));
-            };
-            let eval = evaluate(val.clone(), env.clone())?;
-            env.borrow_mut().set(key, eval.clone());
-            Ok(eval)
+    if let Some(forms) = top_level_do(&ast) {
+        return forms
+            .into_iter()
+            .try_fold(Sexp::Nil, |_, form| evaluate(form, env.clone()));
+    }
+    let expr = resolve_in(&ast, &env)?;
+    eval(&expr, &env, None)
+}
+
+/**
+ * Result of an expression in tail position, where closure calls are deferred
+ * and `recur` hands its slot values back to the enclosing loop
+ */
+enum Tail {
+    Value(Sexp),
+    Call(Closure, Vec<Sexp>),
+    Recur(Vec<(usize, Sexp)>),
+}
+
+/**
+ * Runs deferred tail calls in a loop, so tail recursion doesn't grow the stack.
+ * Leaves skip the tail machinery, since most arguments are leaves.
+ */
+fn eval(expr: &Expr, env: &Env, frame: Option<&Frame>) -> Result<Sexp, String> {
+    match expr {
+        Expr::Const(sexp) => return Ok(sexp.clone()),
+        Expr::Local(address) => return Ok(frame.expect("local outside frame").get(*address)),
+        Expr::Global(global) => return lookup(env, *global),
+        _ => {}
+    }
+    let mut tail = eval_tail(expr, env, frame)?;
+    loop {
+        match tail {
+            Tail::Value(sexp) => return Ok(sexp),
+            Tail::Call(closure, args) => {
+                let (frame, clause) = bind_args(&closure, args)?;
+                tail = eval_tail(&clause.body, env, Some(&frame))?;
+            }
+            Tail::Recur(_) => unreachable!("recur resolved outside a loop"),
         }
-        Sexp::List(list) | Sexp::Vec(list) if first_equal(&list, "let*") => {
-            let ([Sexp::List(list), val] | [Sexp::Vec(list), val]) = &list[1..] else {
-                return Err(format!(
-                    "let* expected [Keys, Val], got {}",
-                    tokens_to_string(&list)
-                ));
-            };
-            if list.len() % 2 == 1 {
-                return Err(format!(
-                    "let* recieved an odd number of atoms on LHS: [{}]",
-                    tokens_to_string(list)
-                ));
+    }
+}
+
+fn eval_tail(expr: &Expr, env: &Env, frame: Option<&Frame>) -> Result<Tail, String> {
+    let value = match expr {
+        Expr::Const(_) | Expr::Local(_) | Expr::Global(_) => eval(expr, env, frame)?,
+        Expr::Def(global, val) => {
+            let eval = eval(val, env, frame)?;
+            define(env, *global, eval.clone())?;
+            eval
+        }
+        Expr::Let(size, bindings, body) => {
+            let frame = frame_new(*size, frame.cloned());
+            for (slot, val) in bindings {
+                let eval = eval(val, env, Some(&frame))?;
+                frame.set(*slot, eval);
+            }
+            return eval_tail(body, env, Some(&frame));
+        }
+        Expr::Loop(size, bindings, body, captured) => {
+            let mut frame = frame_new(*size, frame.cloned());
+            for (slot, val) in bindings {
+                let eval = eval(val, env, Some(&frame))?;
+                frame.set(*slot, eval);
             }
-            let env = env_new(Some(env.clone()));
-            for chunk in list.chunks_exact(2) {
-                let [Sexp::Symbol(sym), expr] = chunk else {
-                    return Err("let* did not recieve Sexp::Symbol".to_string());
-                };
-                let eval = evaluate(expr.clone(), env.clone())?;
-                env.borrow_mut().set(sym, eval);
+            loop {
+                match eval_tail(body, env, Some(&frame))? {
+                    Tail::Recur(values) => {
+                        if *captured {
+                            frame = frame_new(*size, frame.outer());
+                        }
+                        for (slot, val) in values {
+                            frame.set(slot, val);
+                        }
+                    }
+                    tail => return Ok(tail),
+                }
             }
-            evaluate(val.clone(), env.clone())
         }
-        Sexp::List(list) if first_equal(&list, "do") => list
-            .into_iter()
-            .skip(1)
-            .try_fold(Sexp::Nil, |_, sexp| evaluate(sexp, env.clone())),
-        Sexp::List(list) => {
-            let Sexp::List(list) = apply(Sexp::List(list), env)? else {
-                return Err("apply() didn't return Sexp::List".to_string());
-            };
-            let Some(Sexp::Func(func)) = list.first() else {
-                return Err("Evaluated List missing Sexp::Func".to_string());
-            };
-            func(&list[1..])
+        Expr::Recur(_, values) => {
+            return values
+                .iter()
+                .map(|(slot, val)| Ok((*slot, eval(val, env, frame)?)))
+                .collect::<Result<_, String>>()
+                .map(Tail::Recur)
+        }
+        Expr::Do(exprs) => match exprs.split_last() {
+            None => Sexp::Nil,
+            Some((last, init)) => {
+                for expr in init {
+                    eval(expr, env, frame)?;
+                }
+                return eval_tail(last, env, frame);
+            }
+        },
+        Expr::If(cond, then, otherwise) => {
+            return match truthy(&eval(cond, env, frame)?) {
+                true => eval_tail(then, env, frame),
+                false => eval_tail(otherwise, env, frame),
+            }
+        }
+        Expr::Fn(lambda) => Sexp::Closure(Closure {
+            lambda: lambda.clone(),
+            frame: gc::capture(frame),
+        }),
+        Expr::Call(func, args) => {
+            let func = eval(func, env, frame)?;
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, env, frame))
+                .collect::<Result<Vec<Sexp>, _>>()?;
+            match func {
+                Sexp::Func(func) => func.call(&args, env)?,
+                Sexp::Closure(closure) => return Ok(Tail::Call(closure, args)),
+                _ => return Err(NOT_A_FUNCTION.to_string()),
+            }
+        }
+        Expr::Try(body, size, handler) => match eval(body, env, frame) {
+            Ok(sexp) => sexp,
+            Err(error) => {
+                let frame = frame_new(*size, frame.cloned());
+                frame.set(0, caught(env, error));
+                return eval_tail(handler, env, Some(&frame));
+            }
+        },
+        Expr::Vec(exprs) => Sexp::Vec(
+            exprs
+                .iter()
+                .map(|expr| eval(expr, env, frame))
+                .collect::<Result<Vector<Sexp>, _>>()?,
+        ),
+    };
+    Ok(Tail::Value(value))
+}
+
+/**
+ * Calls a native or closure with already evaluated arguments. Natives use
+ * this with the `Env` they were passed to call back into Lisp.
+ */
+pub fn apply(func: &Sexp, args: Vec<Sexp>, env: &Env) -> Result<Sexp, String> {
+    match func {
+        Sexp::Func(func) => func.call(&args, env),
+        Sexp::Closure(closure) => {
+            let (frame, clause) = bind_args(closure, args)?;
+            eval(&clause.body, env, Some(&frame))
         }
-        _ => apply(ast, env),
+        _ => Err(NOT_A_FUNCTION.to_string()),
     }
 }
 
-fn eval_sexps(sexps: Vec<Sexp>, env: Env) -> Result<Vec<Sexp>, String> {
-    sexps
-        .into_iter()
-        .map(|s| evaluate(s, env.clone()))
-        .collect::<Result<Vec<Sexp>, _>>()
+pub const NOT_A_FUNCTION: &str = "Evaluated List missing Sexp::Func";
+
+/** Value of a global, where `env` holds `user` and the other namespaces */
+pub fn lookup(env: &Env, global: Global) -> Result<Sexp, String> {
+    env.borrow()
+        .global(global)
+        .ok_or_else(|| format!("Unknown symbol '{}' found", global))
 }
 
-fn apply(ast: Sexp, env: Env) -> Result<Sexp, String> {
-    match ast {
-        Sexp::Symbol(sym) => env
-            .borrow()
-            .get(sym.as_ref())
-            .ok_or(format!("Unknown symbol '{}' found", sym)),
-        Sexp::List(list) => eval_sexps(list, env).map(Sexp::List),
-        Sexp::Vec(list) => eval_sexps(list, env).map(Sexp::Vec),
-        ast => Ok(ast),
+pub fn define(env: &Env, global: Global, sexp: Sexp) -> Result<(), String> {
+    let mut env = env.borrow_mut();
+    if let Some(from) = env.namespaces.referred(global.ns, global.name) {
+        return Err(format!(
+            "def! cannot define {} in {}, it already refers to {}/{}",
+            global.name, global.ns, from, global.name
+        ));
+    }
+    match global.ns {
+        symbol::USER => env.set(global.name, sexp),
+        ns => match env.namespaces.env(ns) {
+            Some(ns) => ns.borrow_mut().set(global.name, sexp),
+            None => return Err(format!("def! found no namespace {}", ns)),
+        },
+    }
+    Ok(())
+}
+
+/** Only `nil` and `false` are false */
+pub fn truthy(sexp: &Sexp) -> bool {
+    !matches!(sexp, Sexp::Nil | Sexp::Bool(false))
+}
+
+/** Picks the clause of `closure` matching the argument count and builds its frame */
+pub fn bind_args(closure: &Closure, mut args: Vec<Sexp>) -> Result<(Frame, &Clause), String> {
+    let clause = closure.lambda.clause(args.len())?;
+    let frame = frame_new(clause.frame_size, closure.frame.clone());
+    if clause.variadic {
+        let rest = args.split_off(clause.params);
+        frame.set(clause.params, Sexp::List(rest.into()));
+    }
+    for (slot, arg) in args.into_iter().enumerate() {
+        frame.set(slot, arg);
     }
+    Ok((frame, clause))
 }
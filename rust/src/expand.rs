@@ -0,0 +1,112 @@
+use crate::symbol::{self, Symbol};
+use crate::types::{tokens_to_string, Sexp};
+use im_rc::Vector;
+
+/**
+ * Forms `core.mal` defines as macros, which the resolver rewrites into core
+ * special forms before analyzing them: `and`, `or`, `cond`, `->` and `->>`.
+ * A local or global of the same name shadows the form instead.
+ */
+pub fn is_expanded(head: &Sexp) -> bool {
+    matches!(
+        head,
+        Sexp::Symbol(
+            symbol::AND | symbol::OR | symbol::COND | symbol::THREAD_FIRST | symbol::THREAD_LAST
+        )
+    )
+}
+
+/** Rewrites `list`, headed by a form accepted by `is_expanded`, one step */
+pub fn expand(list: &Vector<Sexp>) -> Result<Sexp, String> {
+    let Some(Sexp::Symbol(head)) = list.front() else {
+        unreachable!("resolver checked the head with is_expanded");
+    };
+    let args = list.skip(1);
+    match *head {
+        symbol::AND => Ok(and_or(symbol::AND, args, Sexp::Bool(true))),
+        symbol::OR => Ok(and_or(symbol::OR, args, Sexp::Nil)),
+        symbol::COND => cond(list, args),
+        symbol::THREAD_FIRST => thread(list, args, false),
+        symbol::THREAD_LAST => thread(list, args, true),
+        _ => unreachable!("resolver checked the head with is_expanded"),
+    }
+}
+
+fn list(items: impl IntoIterator<Item = Sexp>) -> Sexp {
+    Sexp::List(items.into_iter().collect())
+}
+
+/**
+ * `(and x more*)` to `(let* (v x) (if v (and more*) v))`, binding `x` only
+ * when evaluating it twice could differ. The space in the slot name keeps
+ * the reader from ever producing it.
+ */
+fn and_or(head: Symbol, mut args: Vector<Sexp>, empty: Sexp) -> Sexp {
+    let Some(first) = args.pop_front() else {
+        return empty;
+    };
+    if args.is_empty() {
+        return first;
+    }
+    let mut rest = args;
+    rest.push_front(Sexp::Symbol(head));
+    let test = |val: Sexp| {
+        let (then, otherwise) = match head {
+            symbol::AND => (Sexp::List(rest.clone()), val.clone()),
+            _ => (val.clone(), Sexp::List(rest.clone())),
+        };
+        list([Sexp::Symbol(symbol::IF), val, then, otherwise])
+    };
+    match first {
+        Sexp::List(_) | Sexp::Vec(_) => {
+            let val = Sexp::Symbol(Symbol::new(&format!("{} value", head)));
+            let bindings = list([val.clone(), first]);
+            list([Sexp::Symbol(symbol::LET), bindings, test(val)])
+        }
+        first => test(first),
+    }
+}
+
+/** `(cond test expr more*)` to `(if test expr (cond more*))`, or nil once empty */
+fn cond(form: &Vector<Sexp>, mut args: Vector<Sexp>) -> Result<Sexp, String> {
+    if args.len() % 2 == 1 {
+        return Err(format!(
+            "cond expected an even number of forms, got {}",
+            tokens_to_string(form)
+        ));
+    }
+    let (Some(test), Some(expr)) = (args.pop_front(), args.pop_front()) else {
+        return Ok(Sexp::Nil);
+    };
+    args.push_front(Sexp::Symbol(symbol::COND));
+    Ok(list([
+        Sexp::Symbol(symbol::IF),
+        test,
+        expr,
+        Sexp::List(args),
+    ]))
+}
+
+/**
+ * `(-> x (f a) g)` to `(g (f x a))`, threading each result in as the first
+ * argument of the next form, or as the last for `->>`
+ */
+fn thread(form: &Vector<Sexp>, mut args: Vector<Sexp>, last: bool) -> Result<Sexp, String> {
+    let Some(init) = args.pop_front() else {
+        return Err(format!(
+            "{} expected at least 1 form, got {}",
+            form[0],
+            tokens_to_string(form)
+        ));
+    };
+    Ok(args.into_iter().fold(init, |acc, step| match step {
+        Sexp::List(mut call) if !call.is_empty() => {
+            match last {
+                true => call.push_back(acc),
+                false => call.insert(1, acc),
+            }
+            Sexp::List(call)
+        }
+        step => list([step, acc]),
+    }))
+}
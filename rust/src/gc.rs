@@ -0,0 +1,165 @@
+use crate::env::{Frame, FrameStruct};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+/** Number of captures between collections never drops below this */
+const MIN_THRESHOLD: usize = 1024;
+
+thread_local! {
+    static CAPTURED: RefCell<Vec<Weak<FrameStruct>>> = const { RefCell::new(Vec::new()) };
+    static THRESHOLD: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+}
+
+/**
+ * Frame for a new closure to capture. Any `Rc` cycle passes through a slot
+ * holding a closure, so only captured frames need to be tracked, and globals
+ * are looked up by name so top level `def!` never forms one.
+ */
+pub fn capture(frame: Option<&Frame>) -> Option<Frame> {
+    let frame = frame?;
+    let tracked = CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        captured.push(Rc::downgrade(frame));
+        captured.len()
+    });
+    if tracked > THRESHOLD.get() {
+        let live = collect_cycles();
+        THRESHOLD.set(MIN_THRESHOLD.max(live * 2));
+    }
+    Some(frame.clone())
+}
+
+/**
+ * Frees unreachable cycles among captured frames by trial deletion, and
+ * returns how many frames are still tracked. A frame referenced from
+ * anywhere other than its tracked neighbours' `outer` or slots is a root.
+ * Lists, Vecs and Maps in slots are unshared first so the closures in them
+ * count as slot references, while closures in Sets, map keys, lazy seqs and
+ * native captures still make their frames roots.
+ */
+pub fn collect_cycles() -> usize {
+    let captured = CAPTURED.with(|captured| captured.take());
+    let mut nodes = HashMap::new();
+    let mut pending = captured
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect::<Vec<Frame>>();
+    while let Some(frame) = pending.pop() {
+        if let Entry::Vacant(entry) = nodes.entry(Rc::as_ptr(&frame)) {
+            frame.unshare();
+            frame.for_each_edge(|child, _| pending.push(child.clone()));
+            entry.insert(frame);
+        }
+    }
+
+    // References from outside the graph, without the handle held in `nodes`
+    let mut external = nodes
+        .iter()
+        .map(|(ptr, frame)| (*ptr, Rc::strong_count(frame) - 1))
+        .collect::<HashMap<_, _>>();
+    for frame in nodes.values() {
+        frame.for_each_edge(|child, owned| {
+            if let (true, Some(refs)) = (owned, external.get_mut(&Rc::as_ptr(child))) {
+                *refs = refs.saturating_sub(1);
+            }
+        });
+    }
+
+    let mut live = HashSet::new();
+    let mut roots = nodes
+        .iter()
+        .filter(|(ptr, _)| external[*ptr] > 0)
+        .map(|(_, frame)| frame)
+        .collect::<Vec<&Frame>>();
+    while let Some(frame) = roots.pop() {
+        if live.insert(Rc::as_ptr(frame)) {
+            frame.for_each_edge(|child, _| roots.push(&nodes[&Rc::as_ptr(child)]));
+        }
+    }
+
+    let mut survivors = Vec::new();
+    for (ptr, frame) in &nodes {
+        match live.contains(ptr) {
+            true => survivors.push(Rc::downgrade(frame)),
+            false => frame.clear(),
+        }
+    }
+    let count = survivors.len();
+    CAPTURED.with(|captured| captured.borrow_mut().extend(survivors));
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::env_core;
+    use crate::env::{self, frames_alive};
+    use crate::tokenizer::Tokenizer;
+    use crate::types::Sexp;
+    use crate::vm;
+
+    const FACT: &str = "(let* (fact (fn* (n) (if (< n 2) 1 (* n (fact (- n 1)))))) (fact 5))";
+
+    #[test]
+    fn test_recursive_closures_are_collected() {
+        let env = env_core();
+        let ast = Sexp::read_from(&mut Tokenizer::new(FACT.to_string())).unwrap();
+        let mut peak = 0;
+        for i in 0..10_000 {
+            let evaluate = if i % 2 == 0 {
+                env::evaluate
+            } else {
+                vm::evaluate
+            };
+            assert_eq!(
+                evaluate(ast.clone(), env.clone()).unwrap().to_string(),
+                "120"
+            );
+            peak = peak.max(frames_alive());
+        }
+        assert!(peak <= 2 * MIN_THRESHOLD, "{} frames alive", peak);
+        collect_cycles();
+        assert_eq!(frames_alive(), 0);
+    }
+
+    #[test]
+    fn test_cycles_through_collections_are_collected() {
+        let env = env_core();
+        let src = "(let* (fs [(fn* () (count fs))] m (assoc {} :f (nth fs 0))) ((get m :f)))";
+        let ast = Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap();
+        let mut peak = 0;
+        for i in 0..10_000 {
+            let evaluate = if i % 2 == 0 {
+                env::evaluate
+            } else {
+                vm::evaluate
+            };
+            assert_eq!(evaluate(ast.clone(), env.clone()).unwrap().to_string(), "1");
+            peak = peak.max(frames_alive());
+        }
+        assert!(peak <= 2 * MIN_THRESHOLD, "{} frames alive", peak);
+        collect_cycles();
+        assert_eq!(frames_alive(), 0);
+    }
+
+    #[test]
+    fn test_reachable_cycles_survive() {
+        let env = env_core();
+        let eval = |src: &str| {
+            let ast = Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap();
+            env::evaluate(ast, env.clone()).unwrap().to_string()
+        };
+        eval("(def! fact (let* (fact (fn* (n) (if (< n 2) 1 (* n (fact (- n 1)))))) fact))");
+        eval("(def! fs (let* (f (fn* () 1)) [f]))");
+        eval("(def! shared (let* (x 7 v [(fn* () x)] h (fn* () v)) v))");
+        collect_cycles();
+        assert_eq!(eval("(fact 5)"), "120");
+        assert_eq!(eval("((nth fs 0))"), "1");
+        assert_eq!(eval("((nth shared 0))"), "7");
+        eval("(def! fact nil)");
+        collect_cycles();
+        assert_eq!(frames_alive(), 2);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::convert::TypedFn;
+use crate::core::{env_bare, env_core};
+use crate::env::{self, Env};
+use crate::lazy;
+use crate::symbol::{self, Symbol};
+use crate::tokenizer::Tokenizer;
+use crate::types::{Arity, Native, Sexp};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/**
+ * Embedding entry point, owning a root `Env` preloaded with the core natives
+ * and the functions of the bundled prelude.
+ * Definitions persist between calls, so it can be fed a script once and then
+ * queried from Rust.
+ *
+ * ```
+ * use mal_rust::interpreter::Interpreter;
+ * use mal_rust::types::Sexp;
+ *
+ * let interp = Interpreter::new();
+ * interp.eval_str("(def! limit 10) (def! allowed? (fn* (n) (< n limit)))").unwrap();
+ * let allowed = interp.call("allowed?", vec![Sexp::Integer(3)]).unwrap();
+ * assert_eq!(allowed.to_string(), "true");
+ * ```
+ */
+pub struct Interpreter {
+    env: Env,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self { env: env_core() }
+    }
+
+    /** Interpreter with only the natives, skipping the bundled prelude */
+    pub fn without_prelude() -> Self {
+        Self { env: env_bare() }
+    }
+
+    /** Root env, for passing to `env::evaluate` or `vm::evaluate` directly */
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /**
+     * Evaluates every form in `src` in order, returning the last result or
+     * `nil`. Lazy seqs in the result are realised as far as printing shows,
+     * so an error raised there is returned rather than printed.
+     */
+    pub fn eval_str(&self, src: &str) -> Result<Sexp, String> {
+        let result = Sexp::read_all(&mut Tokenizer::new(src.to_string()))?
+            .into_iter()
+            .try_fold(Sexp::Nil, |_, ast| env::evaluate(ast, self.env.clone()))?;
+        lazy::realise(&result)?;
+        Ok(result)
+    }
+
+    /** Evaluates the contents of the file at `path`, see `eval_str` */
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<Sexp, String> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)
+            .map_err(|err| format!("load_file could not read {}: {}", path.display(), err))?;
+        self.eval_str(&src)
+    }
+
+    /** Appends a directory searched for the files of required namespaces */
+    pub fn add_load_path(&self, dir: impl Into<PathBuf>) {
+        self.env.borrow_mut().namespaces_mut().add_load_path(dir);
+    }
+
+    /**
+     * Calls the global function `name` with `args`, which are not evaluated
+     * again. `name` is looked up in `user`, or qualified as `ns/name`.
+     */
+    pub fn call(&self, name: &str, args: Vec<Sexp>) -> Result<Sexp, String> {
+        let global = self
+            .env
+            .borrow()
+            .namespaces()
+            .current()
+            .1
+            .global(symbol::USER, Symbol::new(name));
+        let func = env::lookup(&self.env, global)?;
+        env::apply(&func, args, &self.env)
+    }
+
+    /** Binds `name` to `value` in the root env */
+    pub fn define(&self, name: &str, value: Sexp) {
+        self.env.borrow_mut().set(name, value);
+    }
+
+    /** Registers `func` as a native callable from Lisp as `name` */
+    pub fn define_native(
+        &self,
+        name: &str,
+        arity: Arity,
+        func: impl Fn(&[Sexp], &Env) -> Result<Sexp, String> + 'static,
+    ) {
+        self.define(name, Sexp::Func(Native::new(name, arity, func)));
+    }
+
+    /**
+     * Registers a Rust function over `FromSexp` arguments as `name`, checking
+     * the argument count and types before each call, see `Native::typed`
+     */
+    pub fn define_typed<Args, F: TypedFn<Args> + 'static>(&self, name: &str, func: F) {
+        self.define(name, Sexp::Func(Native::typed(name, func)));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Sexp> {
+        self.env.borrow().get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::env::temp_dir;
+    use std::rc::Rc;
+
+    fn twice(args: &[Sexp], _env: &Env) -> Result<Sexp, String> {
+        match args {
+            [Sexp::Integer(n)] => Ok(Sexp::Integer(n * 2)),
+            _ => Err("twice expected 1 Integer".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_eval_str() {
+        let interp = Interpreter::new();
+        let result = interp.eval_str("(def! a 2) ; comment\n(def! b (+ a 1)) [a b]");
+        assert_eq!(result.unwrap().to_string(), "[2 3]");
+        assert_eq!(interp.eval_str("").unwrap().to_string(), "nil");
+        assert_eq!(interp.get("b").unwrap().to_string(), "3");
+        assert!(interp.eval_str("(def! c 1) (missing)").is_err());
+        assert!(interp.get("c").is_some());
+    }
+
+    #[test]
+    fn test_call_and_define_native() {
+        let interp = Interpreter::new();
+        interp.define_native("twice", Arity::exactly(1), twice);
+        interp.define("base", Sexp::Integer(5));
+        interp
+            .eval_str("(def! add-base (fn* (x) (+ base (twice x))))")
+            .unwrap();
+        let result = interp.call("add-base", vec![Sexp::Integer(4)]);
+        assert_eq!(result.unwrap().to_string(), "13");
+        let result = interp.call("+", vec![Sexp::Integer(1), Sexp::Integer(2)]);
+        assert_eq!(result.unwrap().to_string(), "3");
+        assert!(interp.call("add-base", vec![]).is_err());
+        assert!(interp.call("missing", vec![]).is_err());
+        assert!(interp.call("base", vec![]).is_err());
+        assert!(interp.eval_str("(twice 1 2)").is_err());
+    }
+
+    #[test]
+    fn test_stateful_native() {
+        let interp = Interpreter::new();
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        interp.define_native("tick!", Arity::exactly(0), move |_, _| {
+            counter.set(counter.get() + 1);
+            Ok(Sexp::Integer(counter.get()))
+        });
+        let result = interp.eval_str("(tick!) (tick!) [(tick!) (= tick! tick!) (= tick! +)]");
+        assert_eq!(result.unwrap().to_string(), "[3 true false]");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_define_typed() {
+        let interp = Interpreter::new();
+        interp.define_typed("repeat-str", |s: String, n: usize| Ok(s.repeat(n)));
+        let result = interp.eval_str("(repeat-str \"ab\" 3)");
+        assert_eq!(result.unwrap().to_string(), "\"ababab\"");
+        let result = interp.eval_str("(try* (repeat-str \"ab\" -1) (catch* e e))");
+        assert_eq!(
+            result.unwrap().to_string(),
+            "\"repeat-str argument 2: -1 is out of range for usize\""
+        );
+    }
+
+    #[test]
+    fn test_prelude() {
+        let interp = Interpreter::new();
+        let result = interp.eval_str(
+            "[(not nil) ((complement even?) 2) ((partial + 1 2) 3) ((comp inc *) 2 3) (last [1 2 3])]",
+        );
+        assert_eq!(result.unwrap().to_string(), "[true false 6 7 3]");
+        let result = interp.eval_str(
+            "[((comp inc inc *) 2 3) ((comp) 1) (and 1 2) (and) (or nil false) (or (get {} 1) :x)]",
+        );
+        assert_eq!(result.unwrap().to_string(), "[8 1 2 true false :x]");
+        let result = interp.eval_str(
+            "[(cond false 1 (= 1 1) 2 :else 3) (cond) (-> [1 2 3] rest (nth 1) (- 1)) (->> [1 2] (map inc) (reduce +))]",
+        );
+        assert_eq!(result.unwrap().to_string(), "[2 nil 2 5]");
+        let result = interp.eval_str(
+            "(loop (n 3 acc ()) (cond (zero? n) acc :else (recur (dec n) (cons n acc))))",
+        );
+        assert_eq!(result.unwrap().to_string(), "(1 2 3)");
+        assert!(interp
+            .eval_str("(cond 1)")
+            .err()
+            .is_some_and(|err| err.starts_with("cond expected an even number")));
+        let result = interp.eval_str("(ns other) (map identity (list (second [1 2]) (nil? nil)))");
+        assert_eq!(result.unwrap().to_string(), "(2 true)");
+        let shadowed = "[(let* (and (fn* (a b) 42)) (and 1 2)) ((fn* (->) (-> 1)) inc)]";
+        assert_eq!(interp.eval_str(shadowed).unwrap().to_string(), "[42 2]");
+        let ast = Sexp::read_from(&mut Tokenizer::new(shadowed.to_string())).unwrap();
+        let result = crate::vm::evaluate(ast, interp.env().clone());
+        assert_eq!(result.unwrap().to_string(), "[42 2]");
+        interp.eval_str("(def! or (fn* (& xs) :mine))").unwrap();
+        assert_eq!(interp.eval_str("(or 1 2)").unwrap().to_string(), ":mine");
+        assert!(Interpreter::without_prelude()
+            .eval_str("(not nil)")
+            .is_err());
+        assert!(interp
+            .get("inc")
+            .is_some_and(|inc| matches!(inc, Sexp::Func(_))));
+    }
+
+    #[test]
+    fn test_load_file() {
+        let path = temp_dir().join(format!("mal-rust-load-{}.mal", std::process::id()));
+        fs::write(&path, "(def! sq (fn* (x) (* x x)))\n(sq 7)\n").unwrap();
+        let interp = Interpreter::new();
+        assert_eq!(interp.load_file(&path).unwrap().to_string(), "49");
+        fs::remove_file(&path).unwrap();
+        assert!(interp.load_file(&path).is_err());
+    }
+}
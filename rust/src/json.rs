@@ -0,0 +1,468 @@
+use crate::lazy;
+use crate::number::{self, Float};
+use crate::symbol::Symbol;
+use crate::types::{Map, Sexp};
+use num_bigint::BigInt;
+use std::fmt::Write;
+
+/** How JSON object keys are read into `Sexp::Map` keys */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keys {
+    Keyword,
+    String,
+}
+
+/** Deepest nesting of arrays and objects read, as in serde_json */
+pub const MAX_DEPTH: usize = 128;
+
+/**
+ * Parses a single JSON document. Arrays become vectors, `null` becomes `nil`
+ * and numbers without a fraction or exponent stay exact integers.
+ */
+pub fn read_str(src: &str, keys: Keys) -> Result<Sexp, String> {
+    let mut reader = Reader {
+        src,
+        pos: 0,
+        keys,
+        depth: 0,
+    };
+    let sexp = reader.value()?;
+    reader.skip_whitespace();
+    match reader.peek() {
+        None => Ok(sexp),
+        Some(_) => Err(reader.unexpected("end of input")),
+    }
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    pos: usize,
+    keys: Keys,
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!(
+                "JSON expected {}, received '{}' at byte {}",
+                expected, c, self.pos
+            ),
+            None => format!("JSON expected {}, received EOF", expected),
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &str) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() == Some(c) {
+            true => {
+                self.pos += 1;
+                Ok(())
+            }
+            false => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn literal(&mut self, word: &str, sexp: Sexp) -> Result<Sexp, String> {
+        match self.src[self.pos..].starts_with(word) {
+            true => {
+                self.pos += word.len();
+                Ok(sexp)
+            }
+            false => Err(self.unexpected("value")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Sexp, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => self.string().map(Sexp::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.literal("true", Sexp::Bool(true)),
+            Some('f') => self.literal("false", Sexp::Bool(false)),
+            Some('n') => self.literal("null", Sexp::Nil),
+            _ => Err(self.unexpected("value")),
+        }
+    }
+
+    /** Reads an array or object, failing past `MAX_DEPTH` rather than overflowing the stack */
+    fn nested(&mut self, read: fn(&mut Self) -> Result<Sexp, String>) -> Result<Sexp, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!(
+                "JSON nests deeper than {} at byte {}",
+                MAX_DEPTH, self.pos
+            ));
+        }
+        self.depth += 1;
+        let sexp = read(self);
+        self.depth -= 1;
+        sexp
+    }
+
+    fn array(&mut self) -> Result<Sexp, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Sexp::Vec(items.into()));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Sexp::Vec(items.into()));
+                }
+                _ => return Err(self.unexpected("',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Sexp, String> {
+        self.pos += 1;
+        let mut map = Map::default();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Sexp::Map(map));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.unexpected("string key"));
+            }
+            let key = self.string()?;
+            let key = match self.keys {
+                Keys::Keyword => Sexp::Keyword(
+                    Symbol::from_data(&key)
+                        .map_err(|err| format!("JSON {}, read keys as strings instead", err))?,
+                ),
+                Keys::String => Sexp::String(key),
+            };
+            self.expect(':', "':'")?;
+            map.insert(key, self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Sexp::Map(map));
+                }
+                _ => return Err(self.unexpected("',' or '}'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some(c) if c < ' ' => {
+                    self.pos -= 1;
+                    return Err(self.unexpected("escaped control character"));
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.unexpected("'\"'")),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.peek() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                self.pos += 1;
+                let high = self.hex()?;
+                let code = match high {
+                    0xD800..=0xDBFF if self.src[self.pos..].starts_with("\\u") => {
+                        self.pos += 2;
+                        let low = self.hex()?;
+                        0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                    }
+                    _ => high,
+                };
+                return Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            _ => return Err(self.unexpected("escape sequence")),
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self.src.get(self.pos..self.pos + 4);
+        match digits.and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(code) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            None => Err(self.unexpected("4 hex digits")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Sexp, String> {
+        let start = self.pos;
+        let mut exact = true;
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' | '-' | '+' => {}
+                '.' | 'e' | 'E' => exact = false,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text = &self.src[start..self.pos];
+        let invalid = || format!("JSON number '{}' is invalid at byte {}", text, start);
+        if !is_number(text) {
+            return Err(invalid());
+        }
+        if exact {
+            text.parse::<BigInt>()
+                .map(number::normalize)
+                .map_err(|_| invalid())
+        } else {
+            match text.parse::<f64>() {
+                Ok(num) if num.is_finite() => Ok(Sexp::Float(Float(num))),
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
+
+/**
+ * Whether `text` follows the RFC 8259 number grammar: an optional `-`, an
+ * integer part without leading zeros, then optionally `.` and digits, and
+ * `e` or `E` with an optional sign and digits
+ */
+fn is_number(text: &str) -> bool {
+    let digits =
+        |text: &str| text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let int = digits(text);
+    if int == 0 || (int > 1 && text.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &text[int..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        match digits(fraction) {
+            0 => return false,
+            len => rest = &fraction[len..],
+        }
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        match digits(exponent) {
+            0 => return false,
+            len => rest = &exponent[len..],
+        }
+    }
+    rest.is_empty()
+}
+
+/** Prints `sexp` as compact JSON, failing on values JSON cannot represent */
+pub fn write_str(sexp: &Sexp) -> Result<String, String> {
+    let mut out = String::new();
+    write(sexp, &mut out)?;
+    Ok(out)
+}
+
+fn unrepresentable(sexp: &Sexp) -> String {
+    format!(
+        "json-write-str cannot represent {} {}",
+        sexp.type_name(),
+        sexp
+    )
+}
+
+fn write(sexp: &Sexp, out: &mut String) -> Result<(), String> {
+    match sexp {
+        Sexp::Integer(num) => write!(out, "{}", num).unwrap(),
+        Sexp::BigInt(num) => write!(out, "{}", num).unwrap(),
+        Sexp::Ratio(_) | Sexp::Float(_) => match number::to_f64(sexp) {
+            Some(num) if num.is_finite() => write!(out, "{:?}", num).unwrap(),
+            _ => return Err(unrepresentable(sexp)),
+        },
+        Sexp::Bool(boolean) => write!(out, "{}", boolean).unwrap(),
+        Sexp::String(s) => write_string(s, out),
+        Sexp::Keyword(sym) | Sexp::Symbol(sym) => write_string(sym.as_str(), out),
+        Sexp::Char(c) => write_string(&c.to_string(), out),
+        Sexp::List(items) | Sexp::Vec(items) => write_array(items.iter(), out)?,
+        Sexp::Set(set) => write_array(set.iter(), out)?,
+        Sexp::Lazy(_) => {
+            let items = lazy::iter(sexp).collect::<Result<Vec<Sexp>, _>>()?;
+            write_array(items.iter(), out)?
+        }
+        Sexp::Map(map) => {
+            out.push('{');
+            for (i, (key, val)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match key {
+                    Sexp::String(s) => write_string(s, out),
+                    Sexp::Keyword(sym) => write_string(sym.as_str(), out),
+                    _ => {
+                        return Err(format!(
+                            "json-write-str expected String or Keyword keys, received {} {}",
+                            key.type_name(),
+                            key
+                        ))
+                    }
+                }
+                out.push(':');
+                write(val, out)?;
+            }
+            out.push('}');
+        }
+        Sexp::Nil => out.push_str("null"),
+        Sexp::Func(_) | Sexp::Closure(_) | Sexp::Tagged(..) => return Err(unrepresentable(sexp)),
+    }
+    Ok(())
+}
+
+fn write_array<'a>(items: impl Iterator<Item = &'a Sexp>, out: &mut String) -> Result<(), String> {
+    out.push('[');
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write(item, out)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(src: &str) -> String {
+        read_str(src, Keys::Keyword).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_read_str() {
+        assert_eq!(read(r#" {"a": [1, 2.5, -3e2]} "#), "{:a [1 2.5 -300.0]}");
+        assert_eq!(read("[true, false, null, []]"), "[true false nil []]");
+        assert_eq!(read("123456789012345678901234"), "123456789012345678901234");
+        assert_eq!(read("[0, -0, 0.5, 1E+2, 1e-2]"), "[0 0 0.5 100.0 0.01]");
+        assert_eq!(
+            read_str(r#"{"a b": {}}"#, Keys::String)
+                .unwrap()
+                .to_string(),
+            r#"{"a b" {}}"#
+        );
+        let escaped = read_str(r#""tab\t\u00e9\ud83d\ude00""#, Keys::Keyword).unwrap();
+        assert!(escaped == Sexp::String("tab\t\u{e9}\u{1f600}".to_string()));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let err = |src: &str| {
+            read_str(src, Keys::Keyword)
+                .map(|s| s.to_string())
+                .unwrap_err()
+        };
+        assert_eq!(
+            err("[1 2]"),
+            "JSON expected ',' or ']', received '2' at byte 3"
+        );
+        assert_eq!(
+            err("{1: 2}"),
+            "JSON expected string key, received '1' at byte 1"
+        );
+        assert_eq!(err("[1,"), "JSON expected value, received EOF");
+        assert_eq!(
+            err("1 2"),
+            "JSON expected end of input, received '2' at byte 2"
+        );
+        assert_eq!(err("--1"), "JSON number '--1' is invalid at byte 0");
+        assert_eq!(err("01"), "JSON number '01' is invalid at byte 0");
+        assert_eq!(err("[1.]"), "JSON number '1.' is invalid at byte 1");
+        assert_eq!(err("-"), "JSON number '-' is invalid at byte 0");
+        for src in ["-01", "1e", "1e+", "1.e5", "1-2", "[1+2]"] {
+            assert!(read_str(src, Keys::Keyword).is_err(), "{}", src);
+        }
+        assert_eq!(err("+1"), "JSON expected value, received '+' at byte 0");
+        assert!(read_str("\"\\x\"", Keys::Keyword).is_err());
+        assert!(read_str("nul", Keys::Keyword).is_err());
+        let keys = (0..1100)
+            .map(|n| format!("\"{:0>1000}\": 1", n))
+            .collect::<Vec<String>>()
+            .join(",");
+        let many_keys = format!("{{{}}}", keys);
+        assert!(read_str(&many_keys, Keys::Keyword)
+            .is_err_and(|err| err.ends_with("read keys as strings instead")));
+        assert!(read_str(&many_keys, Keys::String).is_ok());
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(read_str(&nested(MAX_DEPTH), Keys::Keyword).is_ok());
+        assert_eq!(
+            err(&nested(20_000)),
+            "JSON nests deeper than 128 at byte 128"
+        );
+        assert!(read_str(&"{\"a\":".repeat(200), Keys::Keyword).is_err());
+    }
+
+    #[test]
+    fn test_write_str() {
+        let sexp = read_str(r#"{"a": [1, 2.5, null, "q\"\n"]}"#, Keys::Keyword).unwrap();
+        assert_eq!(write_str(&sexp).unwrap(), r#"{"a":[1,2.5,null,"q\"\n"]}"#);
+        let map = Sexp::Map(Map::default().update(Sexp::Integer(1), Sexp::Nil));
+        assert_eq!(
+            write_str(&map).unwrap_err(),
+            "json-write-str expected String or Keyword keys, received Integer 1"
+        );
+        let nan = Sexp::Float(Float(f64::NAN));
+        assert_eq!(
+            write_str(&nan).unwrap_err(),
+            "json-write-str cannot represent Float ##NaN"
+        );
+    }
+}
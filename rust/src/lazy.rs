@@ -0,0 +1,425 @@
+use crate::env::{apply, truthy, Env, EnvStruct};
+use crate::number;
+use crate::types::{Arity, Native, Sexp};
+use im_rc::Vector;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::{Rc, Weak};
+
+/** Elements printed before a lazy seq is cut off with `...` */
+pub const PRINT_LIMIT: usize = 100;
+
+/** Computes the rest of a lazy seq, as nil, a List, Vec or another lazy seq */
+type Thunk = Box<dyn FnOnce() -> Result<Sexp, String>>;
+
+enum State {
+    Pending(Thunk),
+    Realizing,
+    Failed(String),
+    Empty,
+    Cons(Sexp, Sexp),
+}
+
+struct Node(RefCell<State>);
+
+/**
+ * A sequence realised one cell at a time on first use. Each cell caches its
+ * head and tail, so walking it again never reruns the thunk, and compares by
+ * identity like closures.
+ */
+#[derive(Clone)]
+pub struct LazySeq(Rc<Node>);
+
+impl LazySeq {
+    pub fn new(thunk: impl FnOnce() -> Result<Sexp, String> + 'static) -> Self {
+        Self::from_state(State::Pending(Box::new(thunk)))
+    }
+
+    /** Already realised cell, as `cons` builds onto a lazy seq */
+    pub fn cons(head: Sexp, tail: Sexp) -> Self {
+        Self::from_state(State::Cons(head, tail))
+    }
+
+    fn from_state(state: State) -> Self {
+        Self(Rc::new(Node(RefCell::new(state))))
+    }
+
+    /** Head and tail of this cell, realising it if needed, or `None` if empty */
+    pub fn step(&self) -> Result<Option<(Sexp, Sexp)>, String> {
+        let state = mem::replace(&mut *self.0 .0.borrow_mut(), State::Realizing);
+        let state = match state {
+            State::Pending(thunk) => match thunk().and_then(|sexp| uncons(&sexp)) {
+                Ok(Some((head, tail))) => State::Cons(head, tail),
+                Ok(None) => State::Empty,
+                Err(err) => State::Failed(err),
+            },
+            State::Realizing => State::Failed("lazy seq depends on its own value".to_string()),
+            state => state,
+        };
+        let result = match &state {
+            State::Cons(head, tail) => Ok(Some((head.clone(), tail.clone()))),
+            State::Empty => Ok(None),
+            State::Failed(err) => Err(err.clone()),
+            State::Pending(_) | State::Realizing => unreachable!("state was realised above"),
+        };
+        *self.0 .0.borrow_mut() = state;
+        result
+    }
+
+    fn id(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+}
+
+/** Drops a long realised chain one cell at a time rather than recursively */
+impl Drop for Node {
+    fn drop(&mut self) {
+        let State::Cons(_, tail) = self.0.get_mut() else {
+            return;
+        };
+        let mut tail = mem::replace(tail, Sexp::Nil);
+        while let Sexp::Lazy(seq) = tail {
+            tail = match Rc::try_unwrap(seq.0) {
+                Ok(mut node) => match node.0.get_mut() {
+                    State::Cons(_, next) => mem::replace(next, Sexp::Nil),
+                    _ => break,
+                },
+                Err(_) => break,
+            };
+        }
+    }
+}
+
+impl PartialEq for LazySeq {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for LazySeq {}
+
+impl PartialOrd for LazySeq {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LazySeq {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id().cmp(&other.id())
+    }
+}
+
+impl Hash for LazySeq {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+/**
+ * Forces up to `PRINT_LIMIT` elements, printing an error in place of the
+ * rest. Printers call `realise` first so such an error is returned instead.
+ */
+impl fmt::Display for LazySeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (i, sexp) in iter(&Sexp::Lazy(self.clone())).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match sexp {
+                _ if i == PRINT_LIMIT => return write!(f, "...)"),
+                Ok(sexp) => write!(f, "{}", sexp)?,
+                Err(err) => return write!(f, "<error: {}>)", err),
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+/**
+ * First element and the rest of a List, Vec, nil or lazy seq, `None` once
+ * it is empty. The rest of a List or Vec is a List.
+ */
+pub fn uncons(sexp: &Sexp) -> Result<Option<(Sexp, Sexp)>, String> {
+    match sexp {
+        Sexp::Lazy(seq) => seq.step(),
+        Sexp::List(list) | Sexp::Vec(list) => Ok(list
+            .front()
+            .map(|head| (head.clone(), Sexp::List(list.skip(1))))),
+        Sexp::Nil => Ok(None),
+        _ => Err(format!(
+            "lazy seq expected a List, Vec or nil, received {} {}",
+            sexp.type_name(),
+            sexp
+        )),
+    }
+}
+
+/**
+ * Realises every lazy seq in `sexp` as far as printing it would, returning
+ * the first error raised on the way
+ */
+pub fn realise(sexp: &Sexp) -> Result<(), String> {
+    match sexp {
+        Sexp::Lazy(_) => {
+            let mut items = iter(sexp);
+            for item in items.by_ref().take(PRINT_LIMIT) {
+                realise(&item?)?;
+            }
+            items.next().transpose().map(|_| ())
+        }
+        Sexp::List(items) | Sexp::Vec(items) => items.iter().try_for_each(realise),
+        Sexp::Map(map) => map.iter().try_for_each(|(key, val)| {
+            realise(key)?;
+            realise(val)
+        }),
+        Sexp::Set(set) => set.iter().try_for_each(realise),
+        Sexp::Tagged(_, sexp) => realise(sexp),
+        _ => Ok(()),
+    }
+}
+
+/**
+ * Orders like the derived `Ord` on `Sexp`, except a lazy seq compares as the
+ * List of its elements. Seqs are walked in step and realised only up to the
+ * first difference, applying the same rule to nested Lists and Vecs.
+ */
+pub fn compare(l: &Sexp, r: &Sexp) -> Result<Ordering, String> {
+    let items = |items: &Vector<Sexp>| items.clone().into_iter().map(Ok);
+    match (l, r) {
+        (Sexp::List(l), Sexp::List(r)) | (Sexp::Vec(l), Sexp::Vec(r)) => {
+            compare_items(items(l), items(r))
+        }
+        (Sexp::List(_) | Sexp::Lazy(_), Sexp::List(_) | Sexp::Lazy(_)) => {
+            compare_items(iter(l), iter(r))
+        }
+        (Sexp::Lazy(_), r) => Ok(Sexp::List(Vector::new()).cmp(r)),
+        (l, Sexp::Lazy(_)) => Ok(l.cmp(&Sexp::List(Vector::new()))),
+        (l, r) => Ok(l.cmp(r)),
+    }
+}
+
+fn compare_items(
+    mut l: impl Iterator<Item = Result<Sexp, String>>,
+    mut r: impl Iterator<Item = Result<Sexp, String>>,
+) -> Result<Ordering, String> {
+    loop {
+        match (l.next().transpose()?, r.next().transpose()?) {
+            (Some(l), Some(r)) => match compare(&l, &r)? {
+                Ordering::Equal => {}
+                ord => return Ok(ord),
+            },
+            (l, r) => return Ok(l.is_some().cmp(&r.is_some())),
+        }
+    }
+}
+
+/** Walks the elements of a List, Vec, nil or lazy seq, realising as it goes */
+pub fn iter(sexp: &Sexp) -> Iter {
+    Iter(Some(sexp.clone()))
+}
+
+pub struct Iter(Option<Sexp>);
+
+impl Iterator for Iter {
+    type Item = Result<Sexp, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match uncons(&self.0.take()?) {
+            Ok(Some((head, tail))) => {
+                self.0 = Some(tail);
+                Some(Ok(head))
+            }
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/**
+ * Env for a thunk that calls back into Lisp. Thunks hold it weakly, since a
+ * lazy seq bound by `def!` would otherwise keep its own env alive.
+ */
+fn upgrade(env: &Weak<RefCell<EnvStruct>>) -> Result<Env, String> {
+    env.upgrade()
+        .ok_or_else(|| "lazy seq realised after its env was dropped".to_string())
+}
+
+/** Native `(lazy-seq body)` resolves to, called with `(fn* () body)` */
+pub fn lazy_seq() -> Sexp {
+    Sexp::Func(Native::new("lazy-seq", Arity::exactly(1), |args, env| {
+        let func = args[0].clone();
+        let env = Rc::downgrade(env);
+        Ok(Sexp::Lazy(LazySeq::new(move || {
+            apply(&func, vec![], &upgrade(&env)?)
+        })))
+    }))
+}
+
+/** `start`, `start + step` and so on while before `end`, forever without one */
+pub fn range(start: Sexp, end: Option<Sexp>, step: Sexp) -> Result<Sexp, String> {
+    let direction = number::compare(&step, &Sexp::Integer(0));
+    let done = match (&end, direction) {
+        (None, _) | (_, Some(Ordering::Equal)) => false,
+        (Some(end), Some(direction)) => number::compare(&start, end) != Some(direction.reverse()),
+        (Some(_), None) => return Err(format!("range expected a number step, received {}", step)),
+    };
+    if done {
+        return Ok(Sexp::List(Default::default()));
+    }
+    let next = number::arithmetic("range", &start, &step, &number::ADD)?;
+    let tail = LazySeq::new(move || range(next, end, step));
+    Ok(Sexp::Lazy(LazySeq::cons(start, Sexp::Lazy(tail))))
+}
+
+/** `x`, `(f x)`, `(f (f x))` and so on */
+pub fn iterate(func: Sexp, x: Sexp, env: &Env) -> Sexp {
+    let env = Rc::downgrade(env);
+    let head = x.clone();
+    let tail = LazySeq::new(move || {
+        let env = upgrade(&env)?;
+        let next = apply(&func, vec![x], &env)?;
+        Ok(iterate(func, next, &env))
+    });
+    Sexp::Lazy(LazySeq::cons(head, Sexp::Lazy(tail)))
+}
+
+/** `x` forever */
+pub fn repeat(x: Sexp) -> Sexp {
+    Sexp::Lazy(LazySeq::new(move || {
+        let tail = repeat(x.clone());
+        Ok(Sexp::Lazy(LazySeq::cons(x, tail)))
+    }))
+}
+
+/** At most the first `n` elements of `coll` */
+pub fn take(n: usize, coll: Sexp) -> Sexp {
+    Sexp::Lazy(LazySeq::new(move || match n {
+        0 => Ok(Sexp::Nil),
+        n => Ok(match uncons(&coll)? {
+            Some((head, tail)) => Sexp::Lazy(LazySeq::cons(head, take(n - 1, tail))),
+            None => Sexp::Nil,
+        }),
+    }))
+}
+
+/** `(func x)` for each `x` of `coll`, applied as elements are realised */
+pub fn map(func: Sexp, coll: Sexp, env: &Env) -> Sexp {
+    let env = Rc::downgrade(env);
+    Sexp::Lazy(LazySeq::new(move || {
+        let Some((head, tail)) = uncons(&coll)? else {
+            return Ok(Sexp::Nil);
+        };
+        let env = upgrade(&env)?;
+        let head = apply(&func, vec![head], &env)?;
+        Ok(Sexp::Lazy(LazySeq::cons(head, map(func, tail, &env))))
+    }))
+}
+
+/** Elements of `coll` satisfying `pred`, skipping ahead as elements are realised */
+pub fn filter(pred: Sexp, coll: Sexp, env: &Env) -> Sexp {
+    let env = Rc::downgrade(env);
+    Sexp::Lazy(LazySeq::new(move || {
+        let env = upgrade(&env)?;
+        let mut coll = coll;
+        while let Some((head, tail)) = uncons(&coll)? {
+            if truthy(&apply(&pred, vec![head.clone()], &env)?) {
+                let tail = filter(pred, tail, &env);
+                return Ok(Sexp::Lazy(LazySeq::cons(head, tail)));
+            }
+            coll = tail;
+        }
+        Ok(Sexp::Nil)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::env;
+    use crate::interpreter::Interpreter;
+    use crate::tokenizer::Tokenizer;
+    use crate::types::{Arity, Sexp};
+    use crate::vm;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_lazy_seqs() {
+        let interp = Interpreter::new();
+        let eval = |src: &str| interp.eval_str(src).map(|sexp| sexp.to_string());
+        assert_eq!(eval("(take 5 (range))").unwrap(), "(0 1 2 3 4)");
+        assert_eq!(eval("(range 1 10 3)").unwrap(), "(1 4 7)");
+        assert_eq!(eval("(range 3 0 -1)").unwrap(), "(3 2 1)");
+        assert_eq!(eval("(range 0)").unwrap(), "()");
+        assert_eq!(
+            eval("(take 4 (filter even? (map inc (iterate (fn* (x) (* x 3)) 1))))").unwrap(),
+            "(2 4 10 28)"
+        );
+        assert_eq!(eval("(take 3 (repeat :x))").unwrap(), "(:x :x :x)");
+        assert_eq!(eval("(repeat 2 nil)").unwrap(), "(nil nil)");
+        assert_eq!(
+            eval("[(first (range 5 9)) (rest (range 2))]").unwrap(),
+            "[5 (1)]"
+        );
+        assert_eq!(
+            eval("[(count (range 1000)) (seq (range 0)) (empty? (range))]").unwrap(),
+            "[1000 nil false]"
+        );
+        assert_eq!(eval("(= (take 3 (range)) (list 0 1 2))").unwrap(), "true");
+        assert_eq!(
+            eval("[(= (range) 1) (= (range) (range 3)) (< (range 2) (list 0 2))]").unwrap(),
+            "[false false true]"
+        );
+        assert_eq!(
+            eval("[(= [(range 2)] [(list 0 1)]) (= (list (range 2)) (list (list 0 1)))]").unwrap(),
+            "[true true]"
+        );
+        assert_eq!(eval("(reduce + (take 100 (range)))").unwrap(), "4950");
+        assert_eq!(eval("(nth (range) 50)").unwrap(), "50");
+        assert_eq!(eval("(cons -1 (range 2))").unwrap(), "(-1 0 1)");
+        let printed = eval("(range)").unwrap();
+        assert!(printed.starts_with("(0 1 2 ") && printed.ends_with(" 98 99 ...)"));
+        let big = "(def! big (range 200000)) (def! n (count big)) (def! big nil) n";
+        assert_eq!(eval(big).unwrap(), "200000");
+    }
+
+    #[test]
+    fn test_lazy_seq_caches() {
+        let interp = Interpreter::new();
+        let eval = |src: &str| interp.eval_str(src).map(|sexp| sexp.to_string());
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        interp.define_native("tick!", Arity::exactly(0), move |_, _| {
+            counter.set(counter.get() + 1);
+            Ok(Sexp::Nil)
+        });
+        eval("(def! nat (fn* (n) (lazy-seq (do (tick!) (cons n (nat (inc n)))))))").unwrap();
+        let define = |src: &str| {
+            let ast = Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap();
+            env::evaluate(ast, interp.env().clone()).unwrap();
+        };
+        define("(def! xs (nat 0))");
+        assert_eq!(
+            eval("[(take 3 xs) (take 5 xs)]").unwrap(),
+            "[(0 1 2) (0 1 2 3 4)]"
+        );
+        assert_eq!(calls.get(), 5);
+        let ast = Sexp::read_from(&mut Tokenizer::new("(take 2 (nat 7))".to_string())).unwrap();
+        let compiled = vm::evaluate(ast, interp.env().clone()).unwrap();
+        assert_eq!(compiled.to_string(), "(7 8)");
+        assert_eq!(eval("(lazy-seq nil)").unwrap(), "()");
+        define("(def! bad (map (fn* (x) (throw \"boom\")) (range)))");
+        assert_eq!(eval("(first bad)").unwrap_err(), "boom");
+        assert_eq!(eval("[1 bad]").unwrap_err(), "boom");
+        assert_eq!(eval("(str (take 1 bad))").unwrap_err(), "boom");
+        assert_eq!(eval("(first bad)").unwrap_err(), "boom");
+        assert_eq!(
+            eval("(try* (count (lazy-seq 1)) (catch* e e))").unwrap(),
+            "\"lazy seq expected a List, Vec or nil, received Integer 1\""
+        );
+    }
+}
@@ -1,5 +1,22 @@
+pub mod compile;
+pub mod convert;
 pub mod core;
+pub mod destructure;
+pub mod edn;
 pub mod env;
+pub mod expand;
+pub mod gc;
+pub mod interpreter;
+pub mod json;
+pub mod lazy;
+pub mod namespace;
+pub mod number;
 pub mod parser;
+pub mod resolve;
+pub mod rng;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod symbol;
 pub mod tokenizer;
 pub mod types;
+pub mod vm;
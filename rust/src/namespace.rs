@@ -0,0 +1,341 @@
+use crate::env::{self, env_new, Env};
+use crate::resolve::Global;
+use crate::symbol::{self, Symbol};
+use crate::tokenizer::Tokenizer;
+use crate::types::{tokens_to_string, Sexp};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/** Names visible in one namespace besides its own definitions */
+#[derive(Default)]
+pub struct Namespace {
+    /** `None` for `user`, whose definitions live in the env holding the table */
+    env: Option<Env>,
+    aliases: HashMap<Symbol, Symbol>,
+    refers: HashMap<Symbol, Symbol>,
+}
+
+impl Namespace {
+    /**
+     * Global a symbol read in namespace `ns` refers to. `alias/name` goes
+     * through the aliases, and bare names through `:refer` before `ns` itself.
+     */
+    pub fn global(&self, ns: Symbol, sym: Symbol) -> Global {
+        if let Some(from) = self.refers.get(&sym) {
+            return Global::new(*from, sym);
+        }
+        match sym.as_str().split_once('/') {
+            Some((qualifier, name)) if !qualifier.is_empty() && !name.is_empty() => {
+                let qualifier = Symbol::new(qualifier);
+                let ns = self.aliases.get(&qualifier).unwrap_or(&qualifier);
+                Global::new(*ns, Symbol::new(name))
+            }
+            _ => Global::new(ns, sym),
+        }
+    }
+}
+
+/**
+ * Registry kept by the env evaluation starts from, which is itself `user`.
+ * Other namespaces are envs over the core natives, so they never see each
+ * other's definitions, and hold no reference back to the registry.
+ */
+pub struct Namespaces {
+    current: Symbol,
+    user: Namespace,
+    others: HashMap<Symbol, Namespace>,
+    load_path: Vec<PathBuf>,
+    loaded: HashSet<Symbol>,
+}
+
+impl Default for Namespaces {
+    fn default() -> Self {
+        Self {
+            current: symbol::USER,
+            user: Namespace::default(),
+            others: HashMap::new(),
+            load_path: vec![PathBuf::from(".")],
+            loaded: HashSet::new(),
+        }
+    }
+}
+
+impl Namespaces {
+    /** Namespace new top level forms are resolved in */
+    pub fn current(&self) -> (Symbol, &Namespace) {
+        (
+            self.current,
+            self.get(self.current).expect("current namespace"),
+        )
+    }
+
+    fn get(&self, ns: Symbol) -> Option<&Namespace> {
+        match ns {
+            symbol::USER => Some(&self.user),
+            ns => self.others.get(&ns),
+        }
+    }
+
+    fn current_mut(&mut self) -> &mut Namespace {
+        match self.current {
+            symbol::USER => &mut self.user,
+            ns => self.others.get_mut(&ns).expect("current namespace"),
+        }
+    }
+
+    /** Namespace `name` was brought into `ns` from by `:refer`, if any */
+    pub fn referred(&self, ns: Symbol, name: Symbol) -> Option<Symbol> {
+        self.get(ns)?.refers.get(&name).copied()
+    }
+
+    /** Env holding the definitions of `ns`, other than `user` */
+    pub fn env(&self, ns: Symbol) -> Option<&Env> {
+        self.others.get(&ns)?.env.as_ref()
+    }
+
+    /** Appends a directory searched by `require` */
+    pub fn add_load_path(&mut self, dir: impl Into<PathBuf>) {
+        self.load_path.push(dir.into());
+    }
+}
+
+fn root(env: &Env) -> Env {
+    match env.borrow().outer() {
+        Some(outer) => root(&outer),
+        None => env.clone(),
+    }
+}
+
+/** Switches to namespace `ns`, creating it over the core natives if needed */
+pub fn enter(env: &Env, ns: Symbol) {
+    let core = root(env);
+    let mut env = env.borrow_mut();
+    let namespaces = env.namespaces_mut();
+    if ns != symbol::USER && !namespaces.others.contains_key(&ns) {
+        let namespace = Namespace {
+            env: Some(env_new(Some(core))),
+            ..Namespace::default()
+        };
+        namespaces.others.insert(ns, namespace);
+    }
+    namespaces.current = ns;
+}
+
+/** File for `ns` relative to a load path entry, `my-lib.core` in `my_lib/core.mal` */
+fn file_name(ns: Symbol) -> PathBuf {
+    let name = ns.as_str().replace('.', "/").replace('-', "_");
+    PathBuf::from(format!("{}.mal", name))
+}
+
+/**
+ * Evaluates the file defining `ns` from the load path, at most once. A
+ * namespace already created in memory needs no file.
+ */
+pub fn load(env: &Env, ns: Symbol) -> Result<(), String> {
+    let (path, exists) = {
+        let env = env.borrow();
+        let namespaces = env.namespaces();
+        if namespaces.loaded.contains(&ns) {
+            return Ok(());
+        }
+        let file = file_name(ns);
+        let path = namespaces
+            .load_path
+            .iter()
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file());
+        (
+            path,
+            ns == symbol::USER || namespaces.others.contains_key(&ns),
+        )
+    };
+    let Some(path) = path else {
+        return match exists {
+            true => Ok(()),
+            false => Err(format!(
+                "require could not find {} on the load path",
+                file_name(ns).display()
+            )),
+        };
+    };
+
+    let previous = env.borrow().namespaces().current;
+    env.borrow_mut().namespaces_mut().loaded.insert(ns);
+    let result = fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|src| Sexp::read_all(&mut Tokenizer::new(src)))
+        .and_then(|forms| {
+            forms
+                .into_iter()
+                .try_for_each(|form| env::evaluate(form, env.clone()).map(|_| ()))
+        });
+    let mut env = env.borrow_mut();
+    let namespaces = env.namespaces_mut();
+    namespaces.current = previous;
+    if let Err(err) = result {
+        namespaces.loaded.remove(&ns);
+        return Err(format!("require {}: {}", path.display(), err));
+    }
+    match namespaces.others.contains_key(&ns) {
+        true => Ok(()),
+        false => Err(format!(
+            "require loaded {}, which does not define namespace {}",
+            path.display(),
+            ns
+        )),
+    }
+}
+
+/**
+ * Loads the namespace named by `spec` and records its `:as` alias and
+ * `:refer` names in the current namespace. `spec` is a symbol, or a vector
+ * such as `[my.lib :as lib :refer [f g]]`.
+ */
+pub fn require(env: &Env, spec: &Sexp) -> Result<(), String> {
+    let invalid = || {
+        format!(
+            "require expected Symbol or [Symbol :as Symbol? :refer [Symbol]?], received {}",
+            spec
+        )
+    };
+    let (ns, options) = match spec {
+        Sexp::Symbol(ns) => (*ns, Vec::new()),
+        Sexp::Vec(spec) | Sexp::List(spec) => match spec.front() {
+            Some(Sexp::Symbol(ns)) => (*ns, spec.iter().skip(1).collect()),
+            _ => return Err(invalid()),
+        },
+        _ => return Err(invalid()),
+    };
+    if options.len() % 2 == 1 {
+        return Err(invalid());
+    }
+    load(env, ns)?;
+
+    let mut env = env.borrow_mut();
+    let target = env.namespaces().env(ns).cloned();
+    let current = env.namespaces().current;
+    let own = env.namespaces().env(current).cloned();
+    for option in options.chunks_exact(2) {
+        match option {
+            [Sexp::Keyword(key), Sexp::Symbol(alias)] if key.as_str() == "as" => {
+                env.namespaces_mut()
+                    .current_mut()
+                    .aliases
+                    .insert(*alias, ns);
+            }
+            [Sexp::Keyword(key), Sexp::Vec(names) | Sexp::List(names)]
+                if key.as_str() == "refer" =>
+            {
+                for name in names {
+                    let Sexp::Symbol(name) = name else {
+                        return Err(invalid());
+                    };
+                    let defined = target
+                        .as_ref()
+                        .is_some_and(|target| target.borrow().defines(*name));
+                    if !defined {
+                        return Err(format!("require :refer found no {} in {}", name, ns));
+                    }
+                    let shadowed = match &own {
+                        Some(own) => own.borrow().defines(*name),
+                        None => env.defines(*name),
+                    };
+                    if shadowed {
+                        return Err(format!(
+                            "require :refer {} conflicts with {} already defined in {}",
+                            name, name, current
+                        ));
+                    }
+                    env.namespaces_mut().current_mut().refers.insert(*name, ns);
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "require expected :as or :refer options, received {}",
+                    tokens_to_string(option.iter().copied())
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_namespaces() {
+        let interp = Interpreter::new();
+        let eval = |src: &str| interp.eval_str(src).map(|sexp| sexp.to_string());
+        eval("(ns a) (def! helper (fn* () :a)) (def! f (fn* () (helper)))").unwrap();
+        eval("(ns b) (def! helper (fn* () :b))").unwrap();
+        assert_eq!(eval("(helper)").unwrap(), ":b");
+        assert_eq!(eval("(a/f)").unwrap(), ":a");
+        assert_eq!(eval("(+ 1 2)").unwrap(), "3");
+        eval("(ns user (:require [a :as x :refer [f]]))").unwrap();
+        assert_eq!(eval("[(x/helper) (f) (b/helper)]").unwrap(), "[:a :a :b]");
+        assert_eq!(eval("helper").unwrap_err(), "Unknown symbol 'helper' found");
+        assert_eq!(eval("a/nope").unwrap_err(), "Unknown symbol 'a/nope' found");
+        assert_eq!(
+            eval("(require '[a :refer [nope]])").unwrap_err(),
+            "require :refer found no nope in a"
+        );
+        assert_eq!(
+            eval("(def! f 5)").unwrap_err(),
+            "def! cannot define f in user, it already refers to a/f"
+        );
+        assert_eq!(eval("(f)").unwrap(), ":a");
+        eval("(def! helper 1)").unwrap();
+        assert_eq!(
+            eval("(require '[a :refer [helper]])").unwrap_err(),
+            "require :refer helper conflicts with helper already defined in user"
+        );
+    }
+
+    #[test]
+    fn test_require_files() {
+        let dir = temp_dir().join(format!("mal-ns-{}", std::process::id()));
+        fs::create_dir_all(dir.join("my_lib")).unwrap();
+        fs::write(
+            dir.join("my_lib/strings.mal"),
+            "(ns my-lib.strings)\n(def! shout (fn* (s) (str s \"!\")))\n",
+        )
+        .unwrap();
+        fs::write(dir.join("counter.mal"), "(ns counter) (def! n 1)").unwrap();
+        fs::write(dir.join("wrong.mal"), "(def! n 1)").unwrap();
+
+        let interp = Interpreter::new();
+        interp.add_load_path(&dir);
+        let eval = |src: &str| interp.eval_str(src).map(|sexp| sexp.to_string());
+        eval("(require '[my-lib.strings :as s])").unwrap();
+        assert_eq!(eval("(s/shout \"hi\")").unwrap(), "\"hi!\"");
+        eval("(require 'counter) (ns counter) (def! n (+ n 1)) (ns user)").unwrap();
+        eval("(require 'counter)").unwrap();
+        assert_eq!(eval("counter/n").unwrap(), "2");
+        assert!(eval("(require 'missing)")
+            .unwrap_err()
+            .contains("could not find missing.mal"));
+        assert!(eval("(require 'wrong)")
+            .unwrap_err()
+            .contains("does not define namespace wrong"));
+        assert_eq!(eval("(do (ns x) 1)").unwrap(), "1");
+        eval("(do (ns zz) (def! q 1) (ns user))").unwrap();
+        assert_eq!(eval("zz/q").unwrap(), "1");
+        assert_eq!(
+            eval("(if true (ns zz))").unwrap_err(),
+            "ns must be at the top level, got (ns zz)"
+        );
+        eval("(in-ns 'zz) (def! r 2) (in-ns 'user)").unwrap();
+        assert_eq!(eval("[zz/q zz/r]").unwrap(), "[1 2]");
+        assert_eq!(
+            eval("(fn* () (in-ns 'zz))").unwrap_err(),
+            "in-ns must be at the top level, got (in-ns (quote zz))"
+        );
+        assert_eq!(eval("((fn* (in-ns) (in-ns 'zz)) identity)").unwrap(), "zz");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
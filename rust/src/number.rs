@@ -0,0 +1,418 @@
+use crate::types::{tokens_to_string as to_str, Sexp};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/**
+ * Largest result `pow` and `bit-shift-left` compute, in bits, so a single
+ * call cannot exhaust memory or run for minutes
+ */
+pub const MAX_BITS: u64 = 1 << 20;
+
+pub type IntOp = fn(i64, i64) -> Option<i64>;
+pub type BigOp = fn(&BigInt, &BigInt) -> Option<BigInt>;
+pub type RatioOp = fn(&BigRational, &BigRational) -> Option<BigRational>;
+pub type FloatOp = fn(f64, f64) -> f64;
+
+/**
+ * One binary operator at every level of the numeric tower. Returning `None`
+ * escalates to the next level, `int` -> `big` -> `ratio`, and `None` from
+ * `ratio` is a division by zero. `float` is used whenever either side is a
+ * `Sexp::Float`.
+ */
+pub struct Ops {
+    pub int: IntOp,
+    pub big: BigOp,
+    pub ratio: RatioOp,
+    pub float: FloatOp,
+}
+
+pub const ADD: Ops = Ops {
+    int: i64::checked_add,
+    big: |x, y| Some(x + y),
+    ratio: |x, y| Some(x + y),
+    float: |x, y| x + y,
+};
+
+pub const SUBTRACT: Ops = Ops {
+    int: i64::checked_sub,
+    big: |x, y| Some(x - y),
+    ratio: |x, y| Some(x - y),
+    float: |x, y| x - y,
+};
+
+pub const MULTIPLY: Ops = Ops {
+    int: i64::checked_mul,
+    big: |x, y| Some(x * y),
+    ratio: |x, y| Some(x * y),
+    float: |x, y| x * y,
+};
+
+/** Integers divide exactly or produce a `Sexp::Ratio` */
+pub const DIVIDE: Ops = Ops {
+    int: |x, y| (x.checked_rem(y)? == 0).then(|| x.checked_div(y))?,
+    big: |x, y| (!y.is_zero() && x.is_multiple_of(y)).then(|| x / y),
+    ratio: |x, y| (!y.is_zero()).then(|| x / y),
+    float: |x, y| x / y,
+};
+
+pub const QUOT: Ops = Ops {
+    int: i64::checked_div,
+    big: |x, y| (!y.is_zero()).then(|| x / y),
+    ratio: |x, y| (!y.is_zero()).then(|| (x / y).trunc()),
+    float: |x, y| (x / y).trunc(),
+};
+
+/** Remainder with the sign of the dividend, `(rem -7 2)` is `-1` */
+pub const REM: Ops = Ops {
+    int: i64::checked_rem,
+    big: |x, y| (!y.is_zero()).then(|| x % y),
+    ratio: |x, y| (!y.is_zero()).then(|| x - y * (x / y).trunc()),
+    float: |x, y| x % y,
+};
+
+/** Remainder with the sign of the divisor, `(mod -7 2)` is `1` */
+pub const MOD: Ops = Ops {
+    int: checked_mod,
+    big: |x, y| (!y.is_zero()).then(|| x.mod_floor(y)),
+    ratio: |x, y| (!y.is_zero()).then(|| x - y * (x / y).floor()),
+    float: float_mod,
+};
+
+/**
+ * `f64` ordered by `f64::total_cmp`, except that `-0.0` equals `0.0`, so `Sexp`
+ * can keep deriving `Eq` and `Ord`. This is what `=` and map keys use, where
+ * `##NaN` equals itself; `==` and `<` go through `compare` and follow IEEE 754.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.0 == other.0 {
+            true => Ordering::Equal,
+            false => self.0.total_cmp(&other.0),
+        }
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 + 0.0).to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for Float {
+    /** Shortest representation that reads back to the same `f64` */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            x if x.is_nan() => write!(f, "##NaN"),
+            f64::INFINITY => write!(f, "##Inf"),
+            f64::NEG_INFINITY => write!(f, "##-Inf"),
+            x => write!(f, "{:?}", x),
+        }
+    }
+}
+
+/** Demotes `big` to `Sexp::Integer` whenever it fits in an `i64` */
+pub fn normalize(big: BigInt) -> Sexp {
+    match big.to_i64() {
+        Some(num) => Sexp::Integer(num),
+        None => Sexp::BigInt(big),
+    }
+}
+
+/** Demotes a whole `ratio` to an integer, `BigRational` is always in lowest terms */
+pub fn normalize_ratio(ratio: BigRational) -> Sexp {
+    if ratio.denom().is_one() {
+        normalize(ratio.to_integer())
+    } else {
+        Sexp::Ratio(ratio)
+    }
+}
+
+pub fn is_number(sexp: &Sexp) -> bool {
+    matches!(
+        sexp,
+        Sexp::Integer(_) | Sexp::BigInt(_) | Sexp::Ratio(_) | Sexp::Float(_)
+    )
+}
+
+pub fn to_big(sexp: &Sexp) -> Option<BigInt> {
+    match sexp {
+        Sexp::Integer(num) => Some(BigInt::from(*num)),
+        Sexp::BigInt(big) => Some(big.clone()),
+        _ => None,
+    }
+}
+
+fn to_ratio(sexp: &Sexp) -> Option<BigRational> {
+    match sexp {
+        Sexp::Ratio(ratio) => Some(ratio.clone()),
+        _ => to_big(sexp).map(BigRational::from_integer),
+    }
+}
+
+pub fn to_f64(sexp: &Sexp) -> Option<f64> {
+    match sexp {
+        Sexp::Integer(num) => Some(*num as f64),
+        Sexp::BigInt(big) => big.to_f64(),
+        Sexp::Ratio(ratio) => ratio.to_f64(),
+        Sexp::Float(Float(num)) => Some(*num),
+        _ => None,
+    }
+}
+
+/** Truncates a finite float towards zero, `None` for `##NaN` and infinities */
+pub fn float_to_int(num: f64) -> Option<Sexp> {
+    BigInt::from_f64(num.trunc()).map(normalize)
+}
+
+/** Applies `ops` to `x` and `y` at the lowest level of the numeric tower that fits */
+pub fn arithmetic(name: &str, x: &Sexp, y: &Sexp, ops: &Ops) -> Result<Sexp, String> {
+    if !is_number(x) || !is_number(y) {
+        return Err(format!(
+            "{} expected numbers, received [{}]",
+            name,
+            to_str(&[x.clone(), y.clone()])
+        ));
+    }
+    if let (Some(a), Some(b)) = (as_float(x, y), as_float(y, x)) {
+        return Ok(Sexp::Float(Float((ops.float)(a, b))));
+    }
+    if let (Sexp::Integer(a), Sexp::Integer(b)) = (x, y) {
+        if let Some(num) = (ops.int)(*a, *b) {
+            return Ok(Sexp::Integer(num));
+        }
+    }
+    if let (Some(a), Some(b)) = (to_big(x), to_big(y)) {
+        if let Some(big) = (ops.big)(&a, &b) {
+            return Ok(normalize(big));
+        }
+    }
+    let (Some(a), Some(b)) = (to_ratio(x), to_ratio(y)) else {
+        unreachable!("Every non-float number converts to a ratio");
+    };
+    (ops.ratio)(&a, &b)
+        .map(normalize_ratio)
+        .ok_or_else(|| format!("{} cannot divide by zero: [{} {}]", name, x, y))
+}
+
+/** `x` as a float if either `x` or `other` is a `Sexp::Float` */
+fn as_float(x: &Sexp, other: &Sexp) -> Option<f64> {
+    match (x, other) {
+        (Sexp::Float(_), _) | (_, Sexp::Float(_)) => to_f64(x),
+        _ => None,
+    }
+}
+
+/** Numeric ordering across representations, `None` for non-numbers and `##NaN` */
+pub fn compare(x: &Sexp, y: &Sexp) -> Option<Ordering> {
+    match (x, y) {
+        (Sexp::Integer(a), Sexp::Integer(b)) => Some(a.cmp(b)),
+        (Sexp::Float(_), _) | (_, Sexp::Float(_)) => to_f64(x)?.partial_cmp(&to_f64(y)?),
+        (Sexp::Ratio(_), _) | (_, Sexp::Ratio(_)) => Some(to_ratio(x)?.cmp(&to_ratio(y)?)),
+        _ => Some(to_big(x)?.cmp(&to_big(y)?)),
+    }
+}
+
+/**
+ * Total order on numbers for sorting: by exact value, which `compare` only
+ * approximates through `f64` for floats, and `##NaN` after every other number
+ */
+pub fn total_compare(x: &Sexp, y: &Sexp) -> Ordering {
+    let rank = |sexp: &Sexp| match sexp {
+        Sexp::Float(Float(num)) if num.is_nan() => 3,
+        Sexp::Float(Float(num)) if *num == f64::INFINITY => 2,
+        Sexp::Float(Float(num)) if *num == f64::NEG_INFINITY => 0,
+        _ => 1,
+    };
+    let exact = |sexp: &Sexp| match sexp {
+        Sexp::Float(Float(num)) => BigRational::from_float(*num),
+        sexp => to_ratio(sexp),
+    };
+    match (x, y) {
+        _ if rank(x) != 1 || rank(y) != 1 => rank(x).cmp(&rank(y)),
+        (Sexp::Float(_), _) | (_, Sexp::Float(_)) => exact(x).cmp(&exact(y)),
+        _ => compare(x, y).unwrap_or(Ordering::Equal),
+    }
+}
+
+/**
+ * Exact for rational bases raised to integer powers, `(pow 2 -2)` is `1/4`.
+ * Any float or a fractional exponent falls back to `f64::powf`.
+ */
+pub fn pow(base: &Sexp, exp: &Sexp) -> Result<Sexp, String> {
+    if !is_number(base) || !is_number(exp) {
+        return Err(format!(
+            "pow expected 2 numbers, received [{} {}]",
+            base, exp
+        ));
+    }
+    match (to_ratio(base), exp) {
+        (Some(ratio), Sexp::Integer(exp)) => {
+            let exp =
+                i32::try_from(*exp).map_err(|_| format!("pow exponent {} is too large", exp))?;
+            if exp < 0 && ratio.is_zero() {
+                return Err(format!("pow cannot divide by zero: [{} {}]", base, exp));
+            }
+            let bits = ratio.numer().bits().max(ratio.denom().bits());
+            if (bits - 1).saturating_mul(exp.unsigned_abs() as u64) > MAX_BITS {
+                return Err(format!(
+                    "pow result would exceed {} bits: [{} {}]",
+                    MAX_BITS, base, exp
+                ));
+            }
+            Ok(normalize_ratio(ratio.pow(exp)))
+        }
+        (Some(_), Sexp::BigInt(_)) => Err(format!("pow exponent {} is too large", exp)),
+        _ => Ok(Sexp::Float(Float(
+            to_f64(base)
+                .unwrap_or(f64::NAN)
+                .powf(to_f64(exp).unwrap_or(f64::NAN)),
+        ))),
+    }
+}
+
+fn expect_integer(name: &str, x: &Sexp) -> Result<BigInt, String> {
+    to_big(x).ok_or_else(|| format!("{} expected an integer, received {}", name, x))
+}
+
+/** Shifts an integer left, promoting to `Sexp::BigInt` instead of dropping bits */
+pub fn shift_left(x: &Sexp, n: u32) -> Result<Sexp, String> {
+    match x {
+        Sexp::Integer(x) if n < 64 && (x << n) >> n == *x => Ok(Sexp::Integer(x << n)),
+        _ => {
+            let x = expect_integer("bit-shift-left", x)?;
+            if !x.is_zero() && x.bits() + n as u64 > MAX_BITS {
+                return Err(format!(
+                    "bit-shift-left result would exceed {} bits: [{} {}]",
+                    MAX_BITS, x, n
+                ));
+            }
+            Ok(normalize(x << n))
+        }
+    }
+}
+
+/** Arithmetic shift right, rounding towards negative infinity */
+pub fn shift_right(x: &Sexp, n: u32) -> Result<Sexp, String> {
+    match x {
+        Sexp::Integer(x) => Ok(Sexp::Integer(x >> n.min(63))),
+        _ => Ok(normalize(expect_integer("bit-shift-right", x)? >> n)),
+    }
+}
+
+fn checked_mod(x: i64, y: i64) -> Option<i64> {
+    let rem = x.checked_rem(y)?;
+    if rem != 0 && (rem < 0) != (y < 0) {
+        rem.checked_add(y)
+    } else {
+        Some(rem)
+    }
+}
+
+fn float_mod(x: f64, y: f64) -> f64 {
+    let rem = x % y;
+    if rem != 0.0 && (rem < 0.0) != (y < 0.0) {
+        rem + y
+    } else {
+        rem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert!(matches!(
+            normalize(BigInt::from(i64::MAX)),
+            Sexp::Integer(i64::MAX)
+        ));
+        assert!(matches!(
+            normalize(BigInt::from(i64::MIN)),
+            Sexp::Integer(i64::MIN)
+        ));
+        assert!(matches!(
+            normalize(BigInt::from(i64::MAX) + 1),
+            Sexp::BigInt(_)
+        ));
+    }
+
+    #[test]
+    fn test_compare() {
+        let big = normalize(BigInt::from(i64::MAX) * 2);
+        let neg = normalize(BigInt::from(i64::MIN) * 2);
+        assert_eq!(compare(&Sexp::Integer(1), &big), Some(Ordering::Less));
+        assert_eq!(compare(&neg, &Sexp::Integer(-1)), Some(Ordering::Less));
+        assert_eq!(compare(&big, &neg), Some(Ordering::Greater));
+        assert_eq!(compare(&big, &big), Some(Ordering::Equal));
+        assert_eq!(compare(&big, &Sexp::Nil), None);
+        assert_eq!(
+            compare(&Sexp::Integer(1), &Sexp::Float(Float(1.5))),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare(&Sexp::Float(Float(f64::NAN)), &big), None);
+    }
+
+    #[test]
+    fn test_normalize_ratio() {
+        let half = BigRational::new(BigInt::from(2), BigInt::from(4));
+        assert_eq!(normalize_ratio(half).to_string(), "1/2");
+        let whole = BigRational::new(BigInt::from(-6), BigInt::from(3));
+        assert!(matches!(normalize_ratio(whole), Sexp::Integer(-2)));
+    }
+
+    #[test]
+    fn test_shifts() {
+        let shl = |x, n| shift_left(&Sexp::Integer(x), n).map(|s| s.to_string());
+        let shr = |x, n| shift_right(&Sexp::Integer(x), n).map(|s| s.to_string());
+        assert_eq!(shl(1, 3).as_deref(), Ok("8"));
+        assert_eq!(shl(1, 63).as_deref(), Ok("9223372036854775808"));
+        assert_eq!(shl(-1, 63).as_deref(), Ok("-9223372036854775808"));
+        assert_eq!(shl(3, 64).as_deref(), Ok("55340232221128654848"));
+        assert_eq!(shr(-9, 1).as_deref(), Ok("-5"));
+        assert_eq!(shr(-9, 100).as_deref(), Ok("-1"));
+        assert!(shift_left(&Sexp::Nil, 1).is_err());
+        assert!(shl(1, MAX_BITS as u32).is_err());
+    }
+
+    #[test]
+    fn test_float_total_order() {
+        assert_eq!(Float(f64::NAN), Float(f64::NAN));
+        assert_eq!(Float(-0.0), Float(0.0));
+        assert!(Float(f64::NEG_INFINITY) < Float(f64::MIN));
+        assert!(Float(f64::INFINITY) < Float(f64::NAN));
+    }
+
+    #[test]
+    fn test_float_display() {
+        assert_eq!(Float(1.0).to_string(), "1.0");
+        assert_eq!(Float(-0.5).to_string(), "-0.5");
+        assert_eq!(Float(1e100).to_string(), "1e100");
+        assert_eq!(Float(1.5e-7).to_string(), "1.5e-7");
+        assert_eq!(Float(f64::NAN).to_string(), "##NaN");
+        assert_eq!(Float(f64::INFINITY).to_string(), "##Inf");
+        assert_eq!(Float(f64::NEG_INFINITY).to_string(), "##-Inf");
+    }
+}
@@ -1,10 +1,18 @@
+use crate::number::{self, Float};
+use crate::symbol::{self, Symbol};
 use crate::tokenizer::Tokenizer;
-use crate::types::Sexp;
+use crate::types::{tokens_to_string, Map, Sexp};
+use im_rc::Vector;
 use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
 use regex::Regex;
 
 lazy_static! {
     static ref INTEGER_RE: Regex = Regex::new(r"^-?\d+$").unwrap();
+    static ref RATIO_RE: Regex = Regex::new(r"^-?\d+/\d+$").unwrap();
+    static ref FLOAT_RE: Regex = Regex::new(r"^-?\d+(\.\d*)?([eE][-+]?\d+)?$").unwrap();
     static ref COMMENT_RE: Regex = Regex::new(r"^;.*$").unwrap();
     static ref KEYWORD_RE: Regex = Regex::new(r"^:.+$").unwrap();
     static ref STRING_RE: Regex = Regex::new(r#"^".*"$"#).unwrap();
@@ -19,60 +27,155 @@ fn read_seq(tokenizer: &mut Tokenizer, closer: &str) -> Result<Vec<Sexp>, String
                 return acc.into_iter().collect::<Result<Vec<Sexp>, _>>();
             }
             Some(_) => acc.push(Sexp::read_from(tokenizer)),
-            None => return Err(format!("Expected {}, recieved EOF", closer)),
+            None => return Err(format!("Expected {}, received EOF", closer)),
         }
     }
 }
 
-fn read_quote(tokenizer: &mut Tokenizer, repr: &str) -> Result<Sexp, String> {
-    let quote = Ok(Sexp::Symbol(String::from(repr)));
+fn read_quote(tokenizer: &mut Tokenizer, repr: Symbol) -> Result<Sexp, String> {
+    let quote = Ok(Sexp::Symbol(repr));
     let contents = Sexp::read_from(tokenizer);
     let sexp = vec![quote, contents];
     sexp.into_iter()
-        .collect::<Result<Vec<Sexp>, _>>()
+        .collect::<Result<Vector<Sexp>, _>>()
         .map(Sexp::List)
 }
 
+fn read_map(tokenizer: &mut Tokenizer) -> Result<Sexp, String> {
+    let forms = read_seq(tokenizer, "}")?;
+    if forms.len() % 2 == 1 {
+        return Err(format!(
+            "Map literal expected an even number of forms, got {{{}}}",
+            tokens_to_string(&forms)
+        ));
+    }
+    let mut forms = forms.into_iter();
+    let mut map = Map::default();
+    while let (Some(key), Some(val)) = (forms.next(), forms.next()) {
+        map.insert(key, val);
+    }
+    Ok(Sexp::Map(map))
+}
+
 impl Sexp {
     /** Parses tokens to AST */
     pub fn read_from(tokenizer: &mut Tokenizer) -> Result<Sexp, String> {
         match tokenizer.next() {
             Some(token) => match token.as_str() {
-                "(" => read_seq(tokenizer, ")").map(Sexp::List),
-                "[" => read_seq(tokenizer, "]").map(Sexp::Vec),
-                "{" => read_seq(tokenizer, "}").map(Sexp::Map),
-                "'" => read_quote(tokenizer, "quote"),
-                "`" => read_quote(tokenizer, "quasiquote"),
-                "~" => read_quote(tokenizer, "unquote"),
-                "@" => read_quote(tokenizer, "deref"),
-                "~@" => read_quote(tokenizer, "splice-unquote"),
+                "(" => read_seq(tokenizer, ")").map(|seq| Sexp::List(seq.into())),
+                "[" => read_seq(tokenizer, "]").map(|seq| Sexp::Vec(seq.into())),
+                "{" => read_map(tokenizer),
+                "'" => read_quote(tokenizer, symbol::QUOTE),
+                "`" => read_quote(tokenizer, symbol::QUASIQUOTE),
+                "~" => read_quote(tokenizer, symbol::UNQUOTE),
+                "@" => read_quote(tokenizer, symbol::DEREF),
+                "~@" => read_quote(tokenizer, symbol::SPLICE_UNQUOTE),
                 "true" => Ok(Sexp::Bool(true)),
                 "false" => Ok(Sexp::Bool(false)),
                 "nil" => Ok(Sexp::Nil),
+                "##NaN" => Ok(Sexp::Float(Float(f64::NAN))),
+                "##Inf" => Ok(Sexp::Float(Float(f64::INFINITY))),
+                "##-Inf" => Ok(Sexp::Float(Float(f64::NEG_INFINITY))),
                 ")" | "]" | "}" => Err(format!("Unexpected token '{}'", token)),
                 "^" => {
-                    let quote = Ok(Sexp::Symbol(String::from("with-meta")));
+                    let quote = Ok(Sexp::Symbol(symbol::WITH_META));
                     let meta = Sexp::read_from(tokenizer);
                     let symbol = Sexp::read_from(tokenizer);
                     [quote, symbol, meta]
                         .into_iter()
-                        .collect::<Result<Vec<Sexp>, _>>()
+                        .collect::<Result<Vector<Sexp>, _>>()
                         .map(Sexp::List)
                 }
                 comment if COMMENT_RE.is_match(comment) => Ok(Sexp::Nil),
-                int if INTEGER_RE.is_match(int) => Ok(Sexp::Integer(
-                    int.parse::<i64>()
-                        .expect("Error: Failed to parse to i64, but matched INTEGER_RE"),
-                )),
+                int if INTEGER_RE.is_match(int) => match int.parse::<i64>() {
+                    Ok(num) => Ok(Sexp::Integer(num)),
+                    Err(_) => Ok(Sexp::BigInt(
+                        int.parse::<BigInt>()
+                            .expect("Error: Failed to parse to BigInt, but matched INTEGER_RE"),
+                    )),
+                },
+                ratio if RATIO_RE.is_match(ratio) => {
+                    let (numer, denom) = ratio
+                        .split_once('/')
+                        .expect("Error: Failed to split ratio, but matched RATIO_RE");
+                    let parse = |int: &str| {
+                        int.parse::<BigInt>()
+                            .expect("Error: Failed to parse to BigInt, but matched RATIO_RE")
+                    };
+                    let denom = parse(denom);
+                    if denom.is_zero() {
+                        return Err(format!("Ratio '{}' has a zero denominator", ratio));
+                    }
+                    Ok(number::normalize_ratio(BigRational::new(
+                        parse(numer),
+                        denom,
+                    )))
+                }
+                float if FLOAT_RE.is_match(float) => {
+                    Ok(Sexp::Float(Float(float.parse::<f64>().expect(
+                        "Error: Failed to parse to f64, but matched FLOAT_RE",
+                    ))))
+                }
                 keyword if KEYWORD_RE.is_match(keyword) => {
-                    Ok(Sexp::Keyword(keyword[1..].to_string()))
+                    Ok(Sexp::Keyword(Symbol::new(&keyword[1..])))
                 }
                 string if STRING_RE.is_match(string) => {
                     Ok(Sexp::String(string[1..string.len() - 1].to_string()))
                 }
-                _ => Ok(Sexp::Symbol(token)),
+                _ => Ok(Sexp::Symbol(Symbol::new(&token))),
             },
             None => Err("Unexpected EOF".to_string()),
         }
     }
+
+    /** Reads every top level form, skipping comments between them */
+    pub fn read_all(tokenizer: &mut Tokenizer) -> Result<Vec<Sexp>, String> {
+        let mut forms = Vec::new();
+        while let Some(token) = tokenizer.peek() {
+            if COMMENT_RE.is_match(token) {
+                tokenizer.next();
+                continue;
+            }
+            forms.push(Sexp::read_from(tokenizer)?);
+        }
+        Ok(forms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(test: &str) -> Result<Sexp, String> {
+        Sexp::read_from(&mut Tokenizer::new(test.to_string()))
+    }
+
+    #[test]
+    fn test_read_symbols() {
+        assert_eq!(read("(a :b 'c)").unwrap().to_string(), "(a :b (quote c))");
+        assert!(read("let*").unwrap() == Sexp::Symbol(symbol::LET));
+        assert!(read(":let*").unwrap() == Sexp::Keyword(symbol::LET));
+        assert!(read("a").unwrap() != read(":a").unwrap());
+    }
+
+    #[test]
+    fn test_read_collections() {
+        assert_eq!(
+            read("(1 [2] {:a 3})").unwrap().to_string(),
+            "(1 [2] {:a 3})"
+        );
+        assert_eq!(read("{:a 1 :a 2}").unwrap().to_string(), "{:a 2}");
+        assert!(read("{:a}").is_err());
+        assert!(read("(1 2").is_err());
+        assert!(read("1/0").is_err());
+    }
+
+    #[test]
+    fn test_read_all() {
+        let read_all = |src: &str| Sexp::read_all(&mut Tokenizer::new(src.to_string()));
+        let forms = read_all("; header\n(def! a 1) ; trailing\n[a]\n").unwrap();
+        assert_eq!(tokens_to_string(&forms), "(def! a 1) [a]");
+        assert!(read_all("").unwrap().is_empty());
+        assert!(read_all("1 (2").is_err());
+    }
 }
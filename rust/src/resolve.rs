@@ -0,0 +1,836 @@
+use crate::compile::{compile, Chunk};
+use crate::destructure;
+use crate::env::{Env, EnvStruct};
+use crate::expand;
+use crate::lazy;
+use crate::namespace::Namespace;
+use crate::symbol::{self, Symbol};
+use crate::types::{tokens_to_string, Sexp};
+use im_rc::Vector;
+use std::cell::OnceCell;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+/** Position of a local: `depth` frames outwards from the current one, at `slot` */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Address {
+    pub depth: usize,
+    pub slot: usize,
+}
+
+/** A global `name` in the namespace it was resolved to */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Global {
+    pub ns: Symbol,
+    pub name: Symbol,
+}
+
+impl Global {
+    pub fn new(ns: Symbol, name: Symbol) -> Self {
+        Self { ns, name }
+    }
+}
+
+/** Names in `user` print bare, as they did before namespaces */
+impl fmt::Display for Global {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ns {
+            symbol::USER => write!(f, "{}", self.name),
+            ns => write!(f, "{}/{}", ns, self.name),
+        }
+    }
+}
+
+/**
+ * A `Sexp` with its special forms decoded and every symbol resolved ahead of
+ * evaluation, so locals are read by index and only globals hit the `Env`
+ */
+pub enum Expr {
+    Const(Sexp),
+    Local(Address),
+    Global(Global),
+    /** `def!` always stores into its namespace, even inside `let*` or `fn*` */
+    Def(Global, Box<Expr>),
+    Let(usize, Vec<(usize, Expr)>, Box<Expr>),
+    Do(Vec<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Fn(Rc<Lambda>),
+    Call(Box<Expr>, Vec<Expr>),
+    Vec(Vec<Expr>),
+    /** Body, then a handler run in a frame of the given size with the error in slot 0 */
+    Try(Box<Expr>, usize, Box<Expr>),
+    /**
+     * A `let*` whose body `recur` jumps back to. The frame is reused across
+     * iterations unless the body creates closures, which may have captured it.
+     */
+    Loop(usize, Vec<(usize, Expr)>, Box<Expr>, bool),
+    /** Stores new values into the innermost loop's slots, `depth` frames out */
+    Recur(usize, Vec<(usize, Expr)>),
+}
+
+/** Code of a `fn*`, with one clause per arity it accepts */
+pub struct Lambda {
+    pub clauses: Vec<Clause>,
+}
+
+impl Lambda {
+    /**
+     * Clause taking `argc` arguments, preferring an exact match over the
+     * variadic clause, or an error listing the arities available
+     */
+    pub fn clause(&self, argc: usize) -> Result<&Clause, String> {
+        let exact = self
+            .clauses
+            .iter()
+            .find(|clause| !clause.variadic && clause.params == argc);
+        let variadic = || {
+            self.clauses
+                .iter()
+                .find(|clause| clause.variadic && clause.params <= argc)
+        };
+        if let Some(clause) = exact.or_else(variadic) {
+            return Ok(clause);
+        }
+        let arities = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                format!(
+                    "{}{}",
+                    clause.params,
+                    if clause.variadic { " or more" } else { "" }
+                )
+            })
+            .collect::<Vec<String>>();
+        Err(match arities.as_slice() {
+            [arity] => format!("fn* expected {} arguments, received {}", arity, argc),
+            arities => format!(
+                "fn* has no clause for {} arguments, expected one of {}",
+                argc,
+                arities.join(", ")
+            ),
+        })
+    }
+}
+
+/** Body of one arity of a `fn*`, with `params` in the first slots of a `frame_size` frame */
+pub struct Clause {
+    pub params: usize,
+    pub variadic: bool,
+    pub frame_size: usize,
+    pub body: Expr,
+    code: OnceCell<Rc<Chunk>>,
+}
+
+impl Clause {
+    /** Bytecode for `body`, compiled on the first call from the VM */
+    pub fn chunk(&self) -> Rc<Chunk> {
+        self.code
+            .get_or_init(|| Rc::new(compile(&self.body)))
+            .clone()
+    }
+}
+
+/** Names of the slots in each enclosing frame, innermost last */
+struct Scope<'a> {
+    frames: Vec<Vec<Symbol>>,
+    /** Fresh slots whose value is being analyzed, only visible from inside a `fn*` */
+    pending: Vec<(usize, usize)>,
+    /** Indices into `frames` of the frames pushed by `fn*` */
+    lambdas: Vec<usize>,
+    /** Namespace free symbols are resolved in */
+    ns: (Symbol, &'a Namespace),
+    /** Enclosing `loop`s a `recur` may target, cleared inside `fn*` */
+    loops: Vec<LoopTarget>,
+    /** Whether the form about to be analyzed is in tail position of the innermost loop */
+    tail: bool,
+    /** Whether the form about to be analyzed is the whole top level form */
+    top: bool,
+    /** Globals defined so far, which shadow the forms `expand` rewrites */
+    env: Option<&'a EnvStruct>,
+}
+
+struct LoopTarget {
+    /** Index into `frames` of the loop's frame */
+    frame: usize,
+    slots: Vec<usize>,
+    /** Set when a `fn*` in the inits or body may capture the frame */
+    captured: bool,
+}
+
+impl Scope<'_> {
+    fn global(&self, symbol: Symbol) -> Global {
+        self.ns.1.global(self.ns.0, symbol)
+    }
+
+    /** Whether `symbol` is bound as a local or a global rather than left to `expand` */
+    fn shadows(&self, symbol: Symbol) -> bool {
+        self.lookup(symbol).is_some()
+            || self
+                .env
+                .is_some_and(|env| env.global(self.global(symbol)).is_some())
+    }
+
+    fn lookup(&self, symbol: Symbol) -> Option<Address> {
+        let deferred = |index: usize| self.lambdas.last().is_some_and(|frame| *frame > index);
+        self.frames
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, frame)| {
+                let slot = frame.iter().position(|sym| *sym == symbol)?;
+                if self.pending.contains(&(index, slot)) && !deferred(index) {
+                    return None;
+                }
+                let depth = self.frames.len() - 1 - index;
+                Some(Address { depth, slot })
+            })
+    }
+
+    /** Slot for `symbol` in the innermost frame, reusing it if already bound there */
+    fn declare(&mut self, symbol: Symbol) -> Option<(usize, bool)> {
+        let frame = self.frames.last_mut()?;
+        Some(match frame.iter().position(|sym| *sym == symbol) {
+            Some(slot) => (slot, false),
+            None => {
+                frame.push(symbol);
+                (frame.len() - 1, true)
+            }
+        })
+    }
+
+    /**
+     * Declares `symbol` before analyzing `val`, so a closure in `val` can call
+     * itself through the slot, while a direct reference still sees the outer binding
+     */
+    fn bind(&mut self, symbol: Symbol, val: &Sexp) -> Result<(Option<usize>, Expr), String> {
+        let declared = self.declare(symbol);
+        let fresh = match declared {
+            Some((slot, true)) => Some((self.frames.len() - 1, slot)),
+            _ => None,
+        };
+        self.pending.extend(fresh);
+        let val = analyze(val, self);
+        if fresh.is_some() {
+            self.pending.pop();
+        }
+        Ok((declared.map(|(slot, _)| slot), val?))
+    }
+}
+
+fn first_equal(list: &Vector<Sexp>, symbol: Symbol) -> bool {
+    matches!(list.front(), Some(Sexp::Symbol(sym)) if *sym == symbol)
+}
+
+/** Resolves a top level form in `user`, where every free symbol is a global */
+pub fn resolve(ast: &Sexp) -> Result<Expr, String> {
+    resolve_with(ast, (symbol::USER, &Namespace::default()), None)
+}
+
+/** Resolves a top level form in the current namespace of `env` */
+pub fn resolve_in(ast: &Sexp, env: &Env) -> Result<Expr, String> {
+    let env = env.borrow();
+    resolve_with(ast, env.namespaces().current(), Some(&env))
+}
+
+fn resolve_with(
+    ast: &Sexp,
+    ns: (Symbol, &Namespace),
+    env: Option<&EnvStruct>,
+) -> Result<Expr, String> {
+    let mut scope = Scope {
+        frames: Vec::new(),
+        pending: Vec::new(),
+        lambdas: Vec::new(),
+        ns,
+        loops: Vec::new(),
+        tail: false,
+        top: true,
+        env,
+    };
+    analyze(ast, &mut scope)
+}
+
+/**
+ * Forms of a top level `do`, which are resolved and evaluated one at a time
+ * so a namespace switch applies to the forms after it
+ */
+pub fn top_level_do(ast: &Sexp) -> Option<Vector<Sexp>> {
+    match ast {
+        Sexp::List(list) if list.len() > 1 && first_equal(list, symbol::DO) => {
+            Some(list.clone().slice(1..))
+        }
+        _ => None,
+    }
+}
+
+/**
+ * `(ns name (:require spec*)*)` is sugar for switching with `in-ns` and then
+ * calling `require`, both with quoted arguments
+ */
+fn analyze_ns(list: &Vector<Sexp>, scope: &Scope) -> Result<Expr, String> {
+    let invalid = || {
+        format!(
+            "ns expected [Symbol, (:require Spec*)*], got {}",
+            tokens_to_string(list)
+        )
+    };
+    let Some(Sexp::Symbol(name)) = list.get(1) else {
+        return Err(invalid());
+    };
+    let call = |func: &str, args: Vec<Sexp>| {
+        let func = Expr::Global(scope.global(Symbol::new(func)));
+        Expr::Call(Box::new(func), args.into_iter().map(Expr::Const).collect())
+    };
+    let mut exprs = vec![call("in-ns", vec![Sexp::Symbol(*name)])];
+    for clause in list.iter().skip(2) {
+        match clause {
+            Sexp::List(clause) if matches!(clause.front(), Some(Sexp::Keyword(key)) if key.as_str() == "require") =>
+            {
+                exprs.push(call("require", clause.iter().skip(1).cloned().collect()));
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(Expr::Do(exprs))
+}
+
+/**
+ * Analyzes `ast` as a tail of the innermost loop if `scope.tail` is set. Only
+ * the forms that pass their tail position on set it again for a subform.
+ */
+fn analyze(ast: &Sexp, scope: &mut Scope) -> Result<Expr, String> {
+    let tail = mem::replace(&mut scope.tail, false);
+    let top = mem::replace(&mut scope.top, false);
+    match ast {
+        Sexp::List(list) if list.is_empty() => Ok(Expr::Const(ast.clone())),
+        Sexp::Vec(list) if list.is_empty() => Ok(Expr::Const(ast.clone())),
+        Sexp::List(list) if first_equal(list, symbol::DEF) => {
+            let forms = list.iter().collect::<Vec<&Sexp>>();
+            let [_, Sexp::Symbol(key), val] = forms.as_slice() else {
+                return Err(format!(
+                    "def! expected [Key, Val], got {}",
+                    tokens_to_string(list)
+                ));
+            };
+            let global = Global::new(scope.ns.0, *key);
+            Ok(Expr::Def(global, Box::new(analyze(val, scope)?)))
+        }
+        Sexp::List(list) | Sexp::Vec(list) if first_equal(list, symbol::LET) => {
+            let forms = list.iter().skip(1).collect::<Vec<&Sexp>>();
+            let ([Sexp::List(bindings), body] | [Sexp::Vec(bindings), body]) = forms.as_slice()
+            else {
+                return Err(format!(
+                    "let* expected [Keys, Val], got {}",
+                    tokens_to_string(list)
+                ));
+            };
+            if bindings.len() % 2 == 1 {
+                return Err(format!(
+                    "let* expected an even number of binding forms, received [{}]",
+                    tokens_to_string(bindings)
+                ));
+            }
+            analyze_let("let*", bindings, body, tail, scope)
+        }
+        Sexp::List(list) if first_equal(list, symbol::LOOP) => analyze_loop(list, scope),
+        Sexp::List(list) if first_equal(list, symbol::RECUR) => {
+            let target = match scope.loops.last() {
+                Some(target) if tail => target,
+                Some(_) => {
+                    return Err(format!(
+                        "recur must be in tail position of loop, got {}",
+                        ast
+                    ))
+                }
+                None => return Err(format!("recur must be inside loop, got {}", ast)),
+            };
+            if list.len() - 1 != target.slots.len() {
+                return Err(format!(
+                    "recur expected {} arguments, received {}",
+                    target.slots.len(),
+                    list.len() - 1
+                ));
+            }
+            let depth = scope.frames.len() - 1 - target.frame;
+            let slots = target.slots.clone();
+            let values = list
+                .iter()
+                .skip(1)
+                .map(|sexp| analyze(sexp, scope))
+                .collect::<Result<Vec<Expr>, _>>()?;
+            Ok(Expr::Recur(depth, slots.into_iter().zip(values).collect()))
+        }
+        Sexp::List(list) if first_equal(list, symbol::QUOTE) => match list.len() {
+            2 => Ok(Expr::Const(list[1].clone())),
+            _ => Err(format!(
+                "quote expected [Form], got {}",
+                tokens_to_string(list)
+            )),
+        },
+        Sexp::List(list) if first_equal(list, symbol::NS) && top => analyze_ns(list, scope),
+        Sexp::List(list)
+            if !top
+                && (first_equal(list, symbol::NS)
+                    || (first_equal(list, symbol::IN_NS)
+                        && scope.lookup(symbol::IN_NS).is_none())) =>
+        {
+            Err(format!("{} must be at the top level, got {}", list[0], ast))
+        }
+        Sexp::List(list)
+            if matches!(list.front(), Some(head @ Sexp::Symbol(sym))
+                if expand::is_expanded(head) && !scope.shadows(*sym)) =>
+        {
+            scope.tail = tail;
+            analyze(&expand::expand(list)?, scope)
+        }
+        Sexp::List(list) if first_equal(list, symbol::LAZY_SEQ) => {
+            let mut body = list.skip(1);
+            body.push_front(Sexp::Symbol(symbol::DO));
+            let thunk = [
+                Sexp::Symbol(symbol::FN),
+                Sexp::List(Vector::new()),
+                Sexp::List(body),
+            ];
+            Ok(Expr::Call(
+                Box::new(Expr::Const(lazy::lazy_seq())),
+                vec![analyze(&Sexp::List(thunk.into_iter().collect()), scope)?],
+            ))
+        }
+        Sexp::List(list) if first_equal(list, symbol::DO) => {
+            let mut exprs = Vec::new();
+            for (i, sexp) in list.iter().enumerate().skip(1) {
+                scope.tail = tail && i == list.len() - 1;
+                exprs.push(analyze(sexp, scope)?);
+            }
+            Ok(Expr::Do(exprs))
+        }
+        Sexp::List(list) if first_equal(list, symbol::IF) => {
+            let forms = list.iter().skip(1).collect::<Vec<&Sexp>>();
+            let (cond, then, otherwise) = match forms.as_slice() {
+                [cond, then] => (cond, then, &&Sexp::Nil),
+                [cond, then, otherwise] => (cond, then, otherwise),
+                _ => {
+                    return Err(format!(
+                        "if expected [Cond, Then, Else?], got {}",
+                        tokens_to_string(list)
+                    ))
+                }
+            };
+            let cond = analyze(cond, scope)?;
+            scope.tail = tail;
+            let then = analyze(then, scope)?;
+            scope.tail = tail;
+            let otherwise = analyze(otherwise, scope)?;
+            Ok(Expr::If(
+                Box::new(cond),
+                Box::new(then),
+                Box::new(otherwise),
+            ))
+        }
+        Sexp::List(list) if first_equal(list, symbol::FN) => {
+            scope
+                .loops
+                .iter_mut()
+                .for_each(|target| target.captured = true);
+            let loops = mem::take(&mut scope.loops);
+            let lambda = analyze_fn(list, scope);
+            scope.loops = loops;
+            Ok(Expr::Fn(Rc::new(lambda?)))
+        }
+        Sexp::List(list) if first_equal(list, symbol::TRY) => {
+            let forms = list.iter().skip(1).collect::<Vec<&Sexp>>();
+            let (body, catch) = match forms.as_slice() {
+                [body] => {
+                    scope.tail = tail;
+                    return analyze(body, scope);
+                }
+                [body, Sexp::List(catch)] if first_equal(catch, symbol::CATCH) => (body, catch),
+                _ => {
+                    return Err(format!(
+                        "try* expected [Body, (catch* Symbol Handler)?], got {}",
+                        tokens_to_string(list)
+                    ))
+                }
+            };
+            let catch_forms = catch.iter().skip(1).collect::<Vec<&Sexp>>();
+            let [Sexp::Symbol(error), handler] = catch_forms.as_slice() else {
+                return Err(format!(
+                    "catch* expected [Symbol, Handler], got {}",
+                    tokens_to_string(catch)
+                ));
+            };
+            let body = analyze(body, scope)?;
+            scope.frames.push(vec![*error]);
+            let handler = analyze(handler, scope);
+            let frame_size = scope.frames.pop().map_or(0, |frame| frame.len());
+            Ok(Expr::Try(Box::new(body), frame_size, Box::new(handler?)))
+        }
+        Sexp::List(list) => {
+            let mut exprs = list
+                .iter()
+                .map(|sexp| analyze(sexp, scope))
+                .collect::<Result<Vec<Expr>, _>>()?;
+            let func = exprs.remove(0);
+            Ok(Expr::Call(Box::new(func), exprs))
+        }
+        Sexp::Vec(list) => Ok(Expr::Vec(
+            list.iter()
+                .map(|sexp| analyze(sexp, scope))
+                .collect::<Result<_, _>>()?,
+        )),
+        Sexp::Symbol(sym) => Ok(match scope.lookup(*sym) {
+            Some(address) => Expr::Local(address),
+            None => Expr::Global(scope.global(*sym)),
+        }),
+        ast => Ok(Expr::Const(ast.clone())),
+    }
+}
+
+/**
+ * `(fn* (params) body)`, or one `([params] body)` clause per arity, at most
+ * one of them variadic. Forms are clauses only when every one is a List
+ * headed by a Vec, so `(fn* ([a b]) body)` still destructures one argument.
+ */
+fn analyze_fn(list: &Vector<Sexp>, scope: &mut Scope) -> Result<Lambda, String> {
+    let forms = list.iter().skip(1).collect::<Vec<&Sexp>>();
+    let is_clause = |form: &&Sexp| match form {
+        Sexp::List(clause) => matches!(clause.front(), Some(Sexp::Vec(_))),
+        _ => false,
+    };
+    let clauses = match forms.as_slice() {
+        forms if !forms.is_empty() && forms.iter().all(is_clause) => forms
+            .iter()
+            .map(|form| match form {
+                Sexp::List(clause) if clause.len() == 2 => match (&clause[0], &clause[1]) {
+                    (Sexp::Vec(params), body) => analyze_clause(params, body, scope),
+                    _ => unreachable!("is_clause checked for params"),
+                },
+                _ => Err(format!(
+                    "fn* expected ([Params] Body) clauses, got {}",
+                    form
+                )),
+            })
+            .collect::<Result<Vec<Clause>, String>>()?,
+        [Sexp::List(params) | Sexp::Vec(params), body] => {
+            vec![analyze_clause(params, body, scope)?]
+        }
+        _ => {
+            return Err(format!(
+                "fn* expected [Params, Body] or ([Params] Body) clauses, got {}",
+                tokens_to_string(list)
+            ))
+        }
+    };
+    for (i, clause) in clauses.iter().enumerate() {
+        let clash = clauses[..i].iter().find(|other| {
+            (clause.variadic && other.variadic)
+                || (!clause.variadic && !other.variadic && clause.params == other.params)
+        });
+        if let Some(other) = clash {
+            return Err(match clause.variadic {
+                true => "fn* can have only one variadic clause".to_string(),
+                false => format!(
+                    "fn* has more than one clause taking {} arguments",
+                    other.params
+                ),
+            });
+        }
+    }
+    Ok(Lambda { clauses })
+}
+
+fn analyze_clause(params: &Vector<Sexp>, body: &Sexp, scope: &mut Scope) -> Result<Clause, String> {
+    let Params {
+        names,
+        variadic,
+        patterns,
+    } = parse_params(params)?;
+    let params = names.len() - variadic as usize;
+    scope.lambdas.push(scope.frames.len());
+    scope.frames.push(names);
+    let body = match patterns.is_empty() {
+        true => analyze(body, scope),
+        false => analyze_let("fn*", &patterns.into(), body, false, scope),
+    };
+    let frame_size = scope.frames.pop().map_or(0, |frame| frame.len());
+    scope.lambdas.pop();
+    Ok(Clause {
+        params,
+        variadic,
+        frame_size,
+        body: body?,
+        code: OnceCell::new(),
+    })
+}
+
+type LetParts = (Vec<(usize, Expr)>, Expr);
+
+/** `let*` frame for `bindings`, destructuring patterns with errors naming `form` */
+fn analyze_let(
+    form: &str,
+    bindings: &Vector<Sexp>,
+    body: &Sexp,
+    tail: bool,
+    scope: &mut Scope,
+) -> Result<Expr, String> {
+    scope.frames.push(Vec::new());
+    let result = analyze_bindings(form, bindings, body, tail, scope);
+    let frame_size = scope.frames.pop().map_or(0, |frame| frame.len());
+    let (bindings, body) = result?;
+    Ok(Expr::Let(frame_size, bindings, Box::new(body)))
+}
+
+fn analyze_bindings(
+    form: &str,
+    bindings: &Vector<Sexp>,
+    body: &Sexp,
+    tail: bool,
+    scope: &mut Scope,
+) -> Result<LetParts, String> {
+    let bindings = bindings.iter().collect::<Vec<&Sexp>>();
+    let mut slots = Vec::new();
+    for chunk in bindings.chunks_exact(2) {
+        let [pattern, expr] = chunk else {
+            unreachable!("chunks_exact(2) always yields pairs");
+        };
+        for (sym, expr) in destructure::bindings(form, pattern, (*expr).clone())? {
+            let (slot, expr) = scope.bind(sym, &expr)?;
+            slots.push((slot.expect("let* pushed a frame"), expr));
+        }
+    }
+    scope.tail = tail;
+    Ok((slots, analyze(body, scope)?))
+}
+
+/**
+ * `(loop (name init ...) body)` binds like `let*`, with each name in a slot
+ * `recur` can overwrite. Patterns are bound to a placeholder name and
+ * destructured again on every iteration.
+ */
+fn analyze_loop(list: &Vector<Sexp>, scope: &mut Scope) -> Result<Expr, String> {
+    let forms = list.iter().skip(1).collect::<Vec<&Sexp>>();
+    let ([Sexp::List(bindings), body] | [Sexp::Vec(bindings), body]) = forms.as_slice() else {
+        return Err(format!(
+            "loop expected [Bindings, Body], got {}",
+            tokens_to_string(list)
+        ));
+    };
+    if bindings.len() % 2 == 1 {
+        return Err(format!(
+            "loop expected an even number of binding forms, got [{}]",
+            tokens_to_string(bindings)
+        ));
+    }
+    scope.frames.push(Vec::new());
+    scope.loops.push(LoopTarget {
+        frame: scope.frames.len() - 1,
+        slots: Vec::new(),
+        captured: false,
+    });
+    let result = analyze_loop_body(bindings, body, scope);
+    let target = scope.loops.pop().expect("loop pushed a target");
+    let frame_size = scope.frames.pop().map_or(0, |frame| frame.len());
+    let (inits, body) = result?;
+    Ok(Expr::Loop(
+        frame_size,
+        inits,
+        Box::new(body),
+        target.captured,
+    ))
+}
+
+type LoopParts = (Vec<(usize, Expr)>, Expr);
+
+fn analyze_loop_body(
+    bindings: &Vector<Sexp>,
+    body: &Sexp,
+    scope: &mut Scope,
+) -> Result<LoopParts, String> {
+    let mut inits = Vec::new();
+    let mut patterns = Vec::new();
+    for (i, pair) in bindings
+        .iter()
+        .collect::<Vec<&Sexp>>()
+        .chunks_exact(2)
+        .enumerate()
+    {
+        let name = match pair[0] {
+            Sexp::Symbol(name) => *name,
+            Sexp::Vec(_) | Sexp::Map(_) => {
+                let name = Symbol::new(&format!("destructure arg {}", i));
+                patterns.extend([pair[0].clone(), Sexp::Symbol(name)]);
+                name
+            }
+            pattern => {
+                return Err(format!(
+                    "loop expected Symbol, Vec or Map bindings, got {}",
+                    pattern
+                ))
+            }
+        };
+        let (slot, init) = scope.bind(name, pair[1])?;
+        inits.push((slot.expect("loop pushed a frame"), init));
+    }
+    let target = scope.loops.last_mut().expect("loop pushed a target");
+    target.slots = inits.iter().map(|(slot, _)| *slot).collect();
+    let body = match patterns.is_empty() {
+        true => {
+            scope.tail = true;
+            analyze(body, scope)
+        }
+        false => analyze_let("loop", &patterns.into(), body, true, scope),
+    };
+    Ok((inits, body?))
+}
+
+/** Parameter names in slot order, and whether the last one collects `& rest` */
+struct Params {
+    names: Vec<Symbol>,
+    variadic: bool,
+    /** `let*` bindings taking apart the params given as patterns */
+    patterns: Vec<Sexp>,
+}
+
+/** Params given as patterns get a placeholder name, destructured by the body */
+fn parse_params(params: &Vector<Sexp>) -> Result<Params, String> {
+    let mut parsed = Params {
+        names: Vec::new(),
+        variadic: false,
+        patterns: Vec::new(),
+    };
+    let mut params = params.iter();
+    while let Some(param) = params.next() {
+        let param = match param {
+            Sexp::Symbol(sym) if *sym == symbol::AMPERSAND => {
+                let (Some(rest), None) = (params.next(), params.next()) else {
+                    return Err("fn* expected a single pattern after &".to_string());
+                };
+                parsed.variadic = true;
+                rest
+            }
+            param => param,
+        };
+        match param {
+            Sexp::Symbol(sym) => parsed.names.push(*sym),
+            Sexp::Vec(_) | Sexp::Map(_) => {
+                let name = Symbol::new(&format!("destructure arg {}", parsed.names.len()));
+                parsed.names.push(name);
+                parsed.patterns.extend([param.clone(), Sexp::Symbol(name)]);
+            }
+            param => {
+                return Err(format!(
+                    "fn* expected Symbol, Vec or Map params, got {}",
+                    param
+                ))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Tokenizer;
+
+    fn resolve_str(src: &str) -> Expr {
+        resolve(&Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_lexical_addresses() {
+        let Expr::Let(1, _, body) = resolve_str("(let* (a 1) (fn* (b) (+ a b)))") else {
+            panic!("expected let*");
+        };
+        let Expr::Fn(lambda) = *body else {
+            panic!("expected fn*");
+        };
+        let Expr::Call(ref func, ref args) = lambda.clauses[0].body else {
+            panic!("expected call");
+        };
+        assert!(matches!(**func, Expr::Global(global) if global.name.as_str() == "+"));
+        assert!(matches!(
+            args.as_slice(),
+            [
+                Expr::Local(Address { depth: 1, slot: 0 }),
+                Expr::Local(Address { depth: 0, slot: 0 })
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_frame_sizes() {
+        assert!(matches!(
+            resolve_str("(let* (a 1 b 2 a 3) a)"),
+            Expr::Let(2, _, _)
+        ));
+        assert!(matches!(
+            resolve_str("(let* (a 1) (def! b a))"),
+            Expr::Let(1, _, _)
+        ));
+        assert!(matches!(resolve_str("(def! x 1)"), Expr::Def(_, _)));
+        let Expr::Fn(lambda) = resolve_str("(fn* (a & more) (do (def! c a) c))") else {
+            panic!("expected fn*");
+        };
+        assert_eq!(
+            (
+                lambda.clauses[0].params,
+                lambda.clauses[0].variadic,
+                lambda.clauses[0].frame_size
+            ),
+            (1, true, 2)
+        );
+    }
+
+    #[test]
+    fn test_self_reference() {
+        let Expr::Let(1, bindings, _) = resolve_str("(let* (a a) a)") else {
+            panic!("expected let*");
+        };
+        assert!(matches!(bindings[0], (0, Expr::Global(_))));
+        let Expr::Let(1, bindings, _) = resolve_str("(let* (f (fn* () f)) f)") else {
+            panic!("expected let*");
+        };
+        let (0, Expr::Fn(lambda)) = &bindings[0] else {
+            panic!("expected fn*");
+        };
+        assert!(matches!(
+            lambda.clauses[0].body,
+            Expr::Local(Address { depth: 1, slot: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_recur_tail_position() {
+        let resolve_err = |src: &str| {
+            let ast = Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap();
+            resolve(&ast).map(|_| ()).unwrap_err()
+        };
+        assert!(matches!(
+            resolve_str("(loop (i 0) (if i (do 1 (recur 2)) (let* (j i) (recur j))))"),
+            Expr::Loop(1, _, _, false)
+        ));
+        assert!(matches!(
+            resolve_str("(loop (i 0) (recur (fn* () i)))"),
+            Expr::Loop(1, _, _, true)
+        ));
+        assert_eq!(
+            resolve_err("(loop (i 0) (do (recur 1) 2))"),
+            "recur must be in tail position of loop, got (recur 1)"
+        );
+        assert_eq!(
+            resolve_err("(loop (i 0) (fn* () (recur 1)))"),
+            "recur must be inside loop, got (recur 1)"
+        );
+        assert_eq!(
+            resolve_err("(loop (i 0) (try* (recur 1) (catch* e e)))"),
+            "recur must be in tail position of loop, got (recur 1)"
+        );
+        assert_eq!(
+            resolve_err("(loop (i 0 j 0) (recur 1))"),
+            "recur expected 2 arguments, received 1"
+        );
+    }
+}
@@ -0,0 +1,603 @@
+use crate::number::{self, Float};
+use crate::symbol::Symbol;
+use crate::types::{Map, Sexp};
+use ::serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use ::serde::{ser, Deserialize, Serialize};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::fmt;
+
+/** Why a value could not be serialized into or deserialized from a `Sexp` */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.0
+    }
+}
+
+/**
+ * Serializes `value` as data. Structs become maps keyed by keywords, unit
+ * variants become keywords, and other variants become a single entry map
+ * from the variant keyword to its contents, such as `{:Circle 1.5}`.
+ */
+pub fn to_sexp<T: Serialize + ?Sized>(value: &T) -> Result<Sexp, Error> {
+    value.serialize(Serializer)
+}
+
+/** Deserializes `T` from data in the shape produced by `to_sexp` */
+pub fn from_sexp<'de, T: Deserialize<'de>>(sexp: &'de Sexp) -> Result<T, Error> {
+    T::deserialize(sexp)
+}
+
+fn keyword(name: &str) -> Sexp {
+    Sexp::Keyword(Symbol::new(name))
+}
+
+fn tagged(variant: &str, value: Sexp) -> Sexp {
+    Sexp::Map(Map::default().update(keyword(variant), value))
+}
+
+/** Builds a `Sexp` from any `Serialize` value */
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Sexp;
+    type Error = Error;
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeVariant<SerializeVec>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = SerializeVariant<SerializeStruct>;
+
+    fn serialize_bool(self, v: bool) -> Result<Sexp, Error> {
+        Ok(Sexp::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Sexp, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Sexp, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Sexp, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Sexp, Error> {
+        Ok(Sexp::Integer(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Sexp, Error> {
+        Ok(number::normalize(BigInt::from(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Sexp, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Sexp, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Sexp, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Sexp, Error> {
+        Ok(number::normalize(BigInt::from(v)))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Sexp, Error> {
+        Ok(number::normalize(BigInt::from(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Sexp, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Sexp, Error> {
+        Ok(Sexp::Float(Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Sexp, Error> {
+        Ok(Sexp::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Sexp, Error> {
+        Ok(Sexp::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Sexp, Error> {
+        Ok(Sexp::Vec(
+            v.iter().map(|b| Sexp::Integer(*b as i64)).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Sexp, Error> {
+        Ok(Sexp::Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Sexp, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Sexp, Error> {
+        Ok(Sexp::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Sexp, Error> {
+        Ok(Sexp::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Sexp, Error> {
+        Ok(keyword(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Sexp, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Sexp, Error> {
+        Ok(tagged(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, Error> {
+        Ok(SerializeVec(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeVec>, Error> {
+        Ok(SerializeVariant(variant, self.serialize_seq(Some(len))?))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap(Map::default(), None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<SerializeStruct, Error> {
+        Ok(SerializeStruct(Map::default()))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeVariant<SerializeStruct>, Error> {
+        Ok(SerializeVariant(variant, SerializeStruct(Map::default())))
+    }
+}
+
+/** Sequences and tuples, serialized as a `Sexp::Vec` */
+pub struct SerializeVec(Vec<Sexp>);
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.0.push(to_sexp(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::Vec(self.0.into()))
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/** Maps keep their keys as serialized, so string keys stay strings */
+pub struct SerializeMap(Map, Option<Sexp>);
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.1 = Some(to_sexp(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .1
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.0.insert(key, to_sexp(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::Map(self.0))
+    }
+}
+
+/** Structs, serialized as a map from field keywords to values */
+pub struct SerializeStruct(Map);
+
+impl ser::SerializeStruct for SerializeStruct {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.0.insert(keyword(key), to_sexp(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::Map(self.0))
+    }
+}
+
+/** Contents of a tuple or struct variant, tagged with its name on `end` */
+pub struct SerializeVariant<S>(&'static str, S);
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeVec> {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(&mut self.1, value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(tagged(self.0, ser::SerializeSeq::end(self.1)?))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeStruct> {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(&mut self.1, key, value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(tagged(self.0, ser::SerializeStruct::end(self.1)?))
+    }
+}
+
+fn unexpected(sexp: &Sexp) -> de::Unexpected<'_> {
+    match sexp {
+        Sexp::Integer(num) => de::Unexpected::Signed(*num),
+        Sexp::Float(num) => de::Unexpected::Float(num.0),
+        Sexp::Bool(boolean) => de::Unexpected::Bool(*boolean),
+        Sexp::String(s) => de::Unexpected::Str(s),
+        Sexp::Char(c) => de::Unexpected::Char(*c),
+        Sexp::List(_) | Sexp::Vec(_) => de::Unexpected::Seq,
+        Sexp::Map(_) => de::Unexpected::Map,
+        Sexp::Nil => de::Unexpected::Unit,
+        _ => de::Unexpected::Other(sexp.type_name()),
+    }
+}
+
+/**
+ * Reads data without evaluating it. Symbols and keywords read as strings,
+ * so they can name struct fields and enum variants, and lists and sets read
+ * like vectors. Functions and tagged elements are rejected.
+ */
+impl<'de> de::Deserializer<'de> for &'de Sexp {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Integer(num) => visitor.visit_i64(*num),
+            Sexp::BigInt(num) => match (num.to_i128(), num.to_u128()) {
+                (Some(num), _) => visitor.visit_i128(num),
+                (_, Some(num)) => visitor.visit_u128(num),
+                _ => Err(de::Error::custom(format!("{} is out of range", num))),
+            },
+            Sexp::Ratio(_) | Sexp::Float(_) => {
+                visitor.visit_f64(number::to_f64(self).expect("Ratio or Float is a number"))
+            }
+            Sexp::Bool(boolean) => visitor.visit_bool(*boolean),
+            Sexp::Symbol(sym) | Sexp::Keyword(sym) => visitor.visit_borrowed_str(sym.as_str()),
+            Sexp::String(s) => visitor.visit_borrowed_str(s),
+            Sexp::Char(c) => visitor.visit_char(*c),
+            Sexp::List(items) | Sexp::Vec(items) => {
+                let mut seq = de::value::SeqDeserializer::<_, Error>::new(items.iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Sexp::Set(set) => {
+                let mut seq = de::value::SeqDeserializer::<_, Error>::new(set.iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Sexp::Map(map) => {
+                let mut entries = de::value::MapDeserializer::<_, Error>::new(map.iter());
+                let value = visitor.visit_map(&mut entries)?;
+                entries.end()?;
+                Ok(value)
+            }
+            Sexp::Nil => visitor.visit_unit(),
+            Sexp::Func(_) | Sexp::Closure(_) | Sexp::Tagged(..) | Sexp::Lazy(_) => Err(
+                de::Error::custom(format!("cannot deserialize {}", self.type_name())),
+            ),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Sexp::Keyword(_) | Sexp::Symbol(_) | Sexp::String(_) => {
+                visitor.visit_enum(Enum(self, None))
+            }
+            Sexp::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().expect("map has one entry");
+                visitor.visit_enum(Enum(variant, Some(value)))
+            }
+            _ => Err(de::Error::invalid_type(unexpected(self), &"enum variant")),
+        }
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for &'de Sexp {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/** A variant name with its contents, `None` for a bare keyword */
+struct Enum<'de>(&'de Sexp, Option<&'de Sexp>);
+
+impl<'de> de::EnumAccess<'de> for Enum<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        Ok((seed.deserialize(self.0)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Enum<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.1 {
+            None | Some(Sexp::Nil) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(unexpected(value), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.contents("newtype variant")?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self.contents("tuple variant")?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_any(self.contents("struct variant")?, visitor)
+    }
+}
+
+impl<'de> Enum<'de> {
+    fn contents(self, expected: &'static str) -> Result<&'de Sexp, Error> {
+        self.1
+            .ok_or_else(|| de::Error::invalid_type(unexpected(self.0), &expected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Tokenizer;
+    use ::serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect(u32, u32),
+        Poly { sides: u8 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        retries: u8,
+        timeout: Option<f64>,
+        tags: Vec<String>,
+        shapes: Vec<Shape>,
+        limits: HashMap<String, i64>,
+    }
+
+    fn read(src: &str) -> Sexp {
+        Sexp::read_from(&mut Tokenizer::new(src.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_to_sexp() {
+        let config = Config {
+            name: "svc".to_string(),
+            retries: 3,
+            timeout: None,
+            tags: vec!["a".to_string()],
+            shapes: vec![Shape::Point, Shape::Circle(1.5)],
+            limits: HashMap::from([("cpu".to_string(), 2)]),
+        };
+        let sexp = to_sexp(&config).unwrap();
+        let expected = r#"{:name "svc" :retries 3 :timeout nil :tags ["a"]
+            :shapes [:Point {:Circle 1.5}] :limits {"cpu" 2}}"#;
+        assert!(sexp == read(expected), "{}", sexp);
+        assert_eq!(
+            to_sexp(&Shape::Rect(1, 2)).unwrap().to_string(),
+            "{:Rect [1 2]}"
+        );
+        assert_eq!(
+            to_sexp(&Shape::Poly { sides: 5 }).unwrap().to_string(),
+            "{:Poly {:sides 5}}"
+        );
+        assert_eq!(
+            to_sexp(&u64::MAX).unwrap().to_string(),
+            "18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn test_from_sexp() {
+        let sexp = read(
+            r#"{:name "svc" :retries 3 :timeout 2.5 :tags ("a" "b")
+                :shapes [:Point {:Rect [1 2]} {:Poly {:sides 5}}] :limits {"cpu" 2}}"#,
+        );
+        let config: Config = from_sexp(&sexp).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "svc".to_string(),
+                retries: 3,
+                timeout: Some(2.5),
+                tags: vec!["a".to_string(), "b".to_string()],
+                shapes: vec![Shape::Point, Shape::Rect(1, 2), Shape::Poly { sides: 5 }],
+                limits: HashMap::from([("cpu".to_string(), 2)]),
+            }
+        );
+        let shape = Shape::Circle(0.5);
+        assert_eq!(
+            from_sexp::<Shape>(&to_sexp(&shape).unwrap()).unwrap(),
+            shape
+        );
+    }
+
+    #[test]
+    fn test_from_sexp_errors() {
+        let err = |src: &str| from_sexp::<Config>(&read(src)).unwrap_err().to_string();
+        assert_eq!(err("{:name \"svc\"}"), "missing field `retries`");
+        assert_eq!(
+            err("{:name 1}"),
+            "invalid type: integer `1`, expected a string"
+        );
+        assert!(err("{:name \"a\" :retries 300}").contains("300"));
+        assert!(from_sexp::<Shape>(&read(":Hexagon")).is_err());
+        assert!(from_sexp::<Shape>(&read("[1 2]")).is_err());
+    }
+}
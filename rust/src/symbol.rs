@@ -0,0 +1,181 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/**
+ * Interned name shared by `Sexp::Symbol` and `Sexp::Keyword`. Equality and
+ * hashing compare ids, ordering compares names so `(< :a :b)` is unchanged.
+ * Names are leaked into the interner of the thread and live for the rest of
+ * the process, so a `Symbol` cannot leave its thread.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+struct Interner {
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, Symbol>,
+    /** Bytes of the names `from_data` has added */
+    data_bytes: usize,
+}
+
+macro_rules! well_known {
+    ($($sym:ident = $id:literal $name:literal,)*) => {
+        $(pub const $sym: Symbol = Symbol($id, PhantomData);)*
+        const WELL_KNOWN: &[&str] = &[$($name),*];
+    };
+}
+
+well_known! {
+    DEF = 0 "def!",
+    LET = 1 "let*",
+    DO = 2 "do",
+    QUOTE = 3 "quote",
+    QUASIQUOTE = 4 "quasiquote",
+    UNQUOTE = 5 "unquote",
+    SPLICE_UNQUOTE = 6 "splice-unquote",
+    DEREF = 7 "deref",
+    WITH_META = 8 "with-meta",
+    IF = 9 "if",
+    FN = 10 "fn*",
+    AMPERSAND = 11 "&",
+    TRY = 12 "try*",
+    CATCH = 13 "catch*",
+    NS = 14 "ns",
+    USER = 15 "user",
+    LAZY_SEQ = 16 "lazy-seq",
+    LOOP = 17 "loop",
+    RECUR = 18 "recur",
+    IN_NS = 19 "in-ns",
+    AND = 20 "and",
+    OR = 21 "or",
+    COND = 22 "cond",
+    THREAD_FIRST = 23 "->",
+    THREAD_LAST = 24 "->>",
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = {
+        let mut interner = Interner {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            data_bytes: 0,
+        };
+        WELL_KNOWN.iter().for_each(|name| {
+            interner.intern(name);
+        });
+        RefCell::new(interner)
+    };
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(sym) = self.ids.get(name) {
+            return *sym;
+        }
+        let name: &'static str = Box::leak(name.into());
+        let sym = Symbol(self.names.len() as u32, PhantomData);
+        self.names.push(name);
+        self.ids.insert(name, sym);
+        sym
+    }
+}
+
+impl Symbol {
+    pub fn new(name: &str) -> Self {
+        INTERNER.with(|interner| interner.borrow_mut().intern(name))
+    }
+
+    /**
+     * Symbol for a name read from data, such as JSON keys and EDN. Names are
+     * never freed, so once the new names added this way pass
+     * `DATA_NAME_BYTES` on this thread, only names already interned can be read.
+     */
+    pub fn from_data(name: &str) -> Result<Self, String> {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(sym) = interner.ids.get(name) {
+                return Ok(*sym);
+            }
+            if interner.data_bytes + name.len() > DATA_NAME_BYTES {
+                return Err(format!(
+                    "cannot intern '{}', data has already added {} bytes of names",
+                    name, interner.data_bytes
+                ));
+            }
+            interner.data_bytes += name.len();
+            Ok(interner.intern(name))
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().names[self.0 as usize])
+    }
+}
+
+/** Bounds the names data readers can leak into the interner of a thread */
+pub const DATA_NAME_BYTES: usize = 1 << 20;
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self == other {
+            true => Ordering::Equal,
+            false => self.as_str().cmp(other.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        assert_eq!(Symbol::new("foo"), Symbol::new("foo"));
+        assert_ne!(Symbol::new("foo"), Symbol::new("bar"));
+        assert_eq!(Symbol::new("foo").as_str(), "foo");
+        assert_eq!(Symbol::new("let*"), LET);
+        assert_eq!(DEF.to_string(), "def!");
+        assert_eq!(WITH_META.as_str(), "with-meta");
+    }
+
+    #[test]
+    fn test_ordering_by_name() {
+        let (b, a) = (Symbol::new("zz-b"), Symbol::new("zz-a"));
+        assert!(a < b);
+        assert_eq!(a.cmp(&a), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_data_names_are_bounded() {
+        let long = |n: usize| format!("{:0>1000}", n);
+        let known = Symbol::new(&long(0));
+        let read = (1..)
+            .map(|n| Symbol::from_data(&long(n)))
+            .take_while(Result::is_ok)
+            .count();
+        assert_eq!(read, DATA_NAME_BYTES / 1000);
+        assert!(Symbol::from_data(&long(read + 1)).is_err());
+        assert_eq!(Symbol::from_data(&long(0)), Ok(known));
+        assert_eq!(Symbol::from_data("let*"), Ok(LET));
+    }
+}
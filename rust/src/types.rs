@@ -1,26 +1,243 @@
+use crate::edn;
+use crate::env::{Env, Frame};
+use crate::lazy::LazySeq;
+use crate::number::Float;
+use crate::resolve::Lambda;
+use crate::symbol::Symbol;
+use im_rc::{HashMap, HashSet, Vector};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::rc::Rc;
 
 // TODO: Convert to Error Enum instead of String?
-pub type Func = fn(&[Sexp]) -> Result<Sexp, String>;
+pub type Func = fn(&[Sexp], &Env) -> Result<Sexp, String>;
 
-// TODO: No clone?
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+/** Native callable, free to capture state such as handles or counters */
+pub type NativeFn = dyn Fn(&[Sexp], &Env) -> Result<Sexp, String>;
+
+/** Number of arguments a native accepts, `max` of `None` meaning unbounded */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub const fn exactly(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub const fn at_least(n: usize) -> Self {
+        Self { min: n, max: None }
+    }
+
+    pub const fn between(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "{} or more", self.min),
+        }
+    }
+}
+
+/** A named Rust function callable from Lisp, compared by identity */
+#[derive(Clone)]
+pub struct Native {
+    name: Symbol,
+    arity: Arity,
+    func: Rc<NativeFn>,
+}
+
+impl Native {
+    pub fn new(
+        name: impl Into<Symbol>,
+        arity: Arity,
+        func: impl Fn(&[Sexp], &Env) -> Result<Sexp, String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arity,
+            func: Rc::new(func),
+        }
+    }
+
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    /** Checks the argument count against `arity` before calling through */
+    pub fn call(&self, args: &[Sexp], env: &Env) -> Result<Sexp, String> {
+        if !self.arity.accepts(args.len()) {
+            return Err(format!(
+                "{} expected {} arguments, received {}",
+                self.name,
+                self.arity,
+                args.len()
+            ));
+        }
+        (self.func)(args, env)
+    }
+
+    fn id(&self) -> usize {
+        Rc::as_ptr(&self.func) as *const () as usize
+    }
+}
+
+impl PartialEq for Native {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Native {}
+
+impl PartialOrd for Native {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Native {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id().cmp(&other.id())
+    }
+}
+
+impl Hash for Native {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+/** A `fn*` value, its resolved body paired with the frame it closed over */
+#[derive(Clone)]
+pub struct Closure {
+    pub lambda: Rc<Lambda>,
+    pub frame: Option<Frame>,
+}
+
+/**
+ * Persistent HAMT keyed with a fixed hasher, so equal maps iterate, print,
+ * hash and compare identically regardless of how they were built.
+ */
+pub type Map = HashMap<Sexp, Sexp, BuildHasherDefault<DefaultHasher>>;
+
+/** Persistent set with the same fixed hasher as `Map` */
+pub type Set = HashSet<Sexp, BuildHasherDefault<DefaultHasher>>;
+
+/** Collections are persistent and share structure, so cloning a `Sexp` is O(1) */
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Sexp {
     Integer(i64),
+    BigInt(BigInt),
+    Ratio(BigRational),
+    Float(Float),
     Bool(bool),
-    Symbol(String),
-    List(Vec<Sexp>),
-    Vec(Vec<Sexp>),
-    Map(Vec<Sexp>),
-    Keyword(String),
+    Symbol(Symbol),
+    List(Vector<Sexp>),
+    Vec(Vector<Sexp>),
+    Map(Map),
+    Set(Set),
+    Keyword(Symbol),
     String(String),
-    Func(Func),
+    Char(char),
+    /** An EDN tagged element left for the host to interpret, as `#inst "..."` */
+    Tagged(Symbol, Box<Sexp>),
+    Lazy(LazySeq),
+    Func(Native),
+    Closure(Closure),
     Nil,
 }
 
-pub fn tokens_to_string(tokens: &[Sexp]) -> String {
+/** Closures compare by the identity of their code and captured frame */
+impl Closure {
+    fn id(&self) -> (usize, usize) {
+        let frame = self
+            .frame
+            .as_ref()
+            .map_or(0, |frame| Rc::as_ptr(frame) as usize);
+        (Rc::as_ptr(&self.lambda) as usize, frame)
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Closure {}
+
+impl PartialOrd for Closure {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Closure {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id().cmp(&other.id())
+    }
+}
+
+impl Hash for Closure {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl Sexp {
+    /** Variant name, as used in error messages */
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Sexp::Integer(_) => "Integer",
+            Sexp::BigInt(_) => "BigInt",
+            Sexp::Ratio(_) => "Ratio",
+            Sexp::Float(_) => "Float",
+            Sexp::Bool(_) => "Bool",
+            Sexp::Symbol(_) => "Symbol",
+            Sexp::List(_) => "List",
+            Sexp::Vec(_) => "Vec",
+            Sexp::Map(_) => "Map",
+            Sexp::Set(_) => "Set",
+            Sexp::Keyword(_) => "Keyword",
+            Sexp::String(_) => "String",
+            Sexp::Char(_) => "Char",
+            Sexp::Tagged(..) => "Tagged",
+            Sexp::Lazy(_) => "LazySeq",
+            Sexp::Func(_) | Sexp::Closure(_) => "Func",
+            Sexp::Nil => "Nil",
+        }
+    }
+}
+
+pub fn tokens_to_string<'a>(tokens: impl IntoIterator<Item = &'a Sexp>) -> String {
     tokens
-        .iter()
+        .into_iter()
         .map(|s| s.to_string())
         .collect::<Vec<String>>()
         .join(" ")
@@ -30,6 +247,9 @@ impl fmt::Display for Sexp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Sexp::Integer(num) => write!(f, "{}", num),
+            Sexp::BigInt(num) => write!(f, "{}", num),
+            Sexp::Ratio(num) => write!(f, "{}", num),
+            Sexp::Float(num) => write!(f, "{}", num),
             Sexp::Bool(boolean) => write!(f, "{}", boolean),
             Sexp::Symbol(sym) => write!(f, "{}", sym),
             Sexp::Keyword(sym) => write!(f, ":{}", sym),
@@ -42,9 +262,17 @@ impl fmt::Display for Sexp {
             ),
             Sexp::Nil => write!(f, "nil"),
             Sexp::List(tokens) => write!(f, "({})", tokens_to_string(tokens)),
-            Sexp::Map(tokens) => write!(f, "{{{}}}", tokens_to_string(tokens)),
+            Sexp::Map(map) => write!(
+                f,
+                "{{{}}}",
+                tokens_to_string(map.iter().flat_map(|(key, val)| [key, val]))
+            ),
             Sexp::Vec(tokens) => write!(f, "[{}]", tokens_to_string(tokens)),
-            Sexp::Func(_) => write!(f, "<func>"),
+            Sexp::Set(set) => write!(f, "#{{{}}}", tokens_to_string(set)),
+            Sexp::Char(c) => write!(f, "{}", edn::char_name(*c)),
+            Sexp::Tagged(tag, sexp) => write!(f, "#{} {}", tag, sexp),
+            Sexp::Lazy(seq) => write!(f, "{}", seq),
+            Sexp::Func(_) | Sexp::Closure(_) => write!(f, "<func>"),
         }
     }
 }